@@ -0,0 +1,73 @@
+//! Benchmarks firing a net with and without compiling its flows.
+//!
+//! Run with `cargo bench --bench compiled`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use petnat::{Nn, PetriNet, Pn, Tn, Token, W};
+
+type Star = Nn<0>;
+
+const TOKENS: usize = 1_000;
+const ROUNDS: usize = 1_000;
+const SAMPLES: u32 = 10;
+
+// Weighted star whose marks are sent back by a second transition, so that it fires forever.
+fn weighted_star() -> PetriNet<Star> {
+    PetriNet::new()
+        .add_place::<Pn<0>>()
+        .add_place::<Pn<1>>()
+        .add_place::<Pn<2>>()
+        .add_place::<Pn<3>>()
+        .add_place::<Pn<4>>()
+        .add_trans::<Tn<0>, ((Pn<0>, W<1>), (Pn<1>, W<2>)), ((Pn<2>, W<1>), (Pn<3>, W<2>), (Pn<4>, W<3>))>()
+        .add_trans::<Tn<1>, ((Pn<2>, W<1>), (Pn<3>, W<2>), (Pn<4>, W<3>)), ((Pn<0>, W<1>), (Pn<1>, W<2>))>()
+}
+
+fn spawn(net: &PetriNet<Star>) -> Vec<Token<Star>> {
+    (0..TOKENS)
+        .map(|_| {
+            let mut token = net.spawn_token();
+            net.mark::<Pn<0>>(&mut token, 1);
+            net.mark::<Pn<1>>(&mut token, 2);
+            token
+        })
+        .collect()
+}
+
+fn bench(name: &str, net: &PetriNet<Star>, fire: impl Fn(&mut Token<Star>)) {
+    let mut total = Duration::ZERO;
+    for _ in 0..SAMPLES {
+        let mut tokens = spawn(net);
+        let start = Instant::now();
+        for _ in 0..ROUNDS {
+            for token in &mut tokens {
+                fire(black_box(token));
+            }
+        }
+        total += start.elapsed();
+    }
+    let firings = u32::try_from(TOKENS * ROUNDS * 2).unwrap_or(u32::MAX);
+    println!(
+        "{name:<8} {:>10.2?} per sample, {:>8.2?} per firing",
+        total / SAMPLES,
+        total / SAMPLES / firings
+    );
+}
+
+fn main() {
+    let net = weighted_star();
+    // Look up the transitions once, like a hot loop would.
+    let (t0, _) = net.trans::<Tn<0>>();
+    let (t1, _) = net.trans::<Tn<1>>();
+    bench("dynamic", &net, |token| {
+        let _ = black_box(net.fire_by_id(t0, token));
+        let _ = black_box(net.fire_by_id(t1, token));
+    });
+    let compiled = weighted_star().compile();
+    bench("compiled", &compiled, |token| {
+        let _ = black_box(compiled.fire_by_id(t0, token));
+        let _ = black_box(compiled.fire_by_id(t1, token));
+    });
+}
@@ -0,0 +1,66 @@
+//! Benchmarks spawning and firing many tokens of a small net.
+//!
+//! Run with `cargo bench --bench tokens`, then with `--features smallvec`
+//! to compare tokens stored on the heap with tokens stored inline.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use petnat::{Nn, PetriNet, Pn, Tn, Token, W};
+
+type Ring = Nn<0>;
+
+const TOKENS: usize = 10_000;
+const ROUNDS: usize = 100;
+const SAMPLES: u32 = 10;
+
+fn ring() -> PetriNet<Ring> {
+    PetriNet::new()
+        .add_place::<Pn<0>>()
+        .add_place::<Pn<1>>()
+        .add_trans::<Tn<0>, (Pn<0>, W<1>), (Pn<1>, W<1>)>()
+        .add_trans::<Tn<1>, (Pn<1>, W<1>), (Pn<0>, W<1>)>()
+}
+
+fn spawn(net: &PetriNet<Ring>) -> Vec<Token<Ring>> {
+    (0..TOKENS)
+        .map(|_| {
+            let mut token = net.spawn_token();
+            net.mark::<Pn<0>>(&mut token, 1);
+            token
+        })
+        .collect()
+}
+
+fn fire(net: &PetriNet<Ring>, tokens: &mut [Token<Ring>]) {
+    for _ in 0..ROUNDS {
+        for token in tokens.iter_mut() {
+            let _ = black_box(net.fire::<Tn<0>>(token));
+            let _ = black_box(net.fire::<Tn<1>>(token));
+        }
+    }
+}
+
+fn report(name: &str, total: Duration, ops: usize) {
+    let per_op = total / SAMPLES / u32::try_from(ops).unwrap_or(u32::MAX);
+    println!(
+        "{name:<8} {:>10.2?} per sample, {per_op:>8.2?} per op",
+        total / SAMPLES
+    );
+}
+
+fn main() {
+    let net = ring();
+    let mut spawning = Duration::ZERO;
+    let mut firing = Duration::ZERO;
+    for _ in 0..SAMPLES {
+        let start = Instant::now();
+        let mut tokens = black_box(spawn(&net));
+        spawning += start.elapsed();
+        let start = Instant::now();
+        fire(&net, &mut tokens);
+        firing += start.elapsed();
+    }
+    report("spawn", spawning, TOKENS);
+    report("fire", firing, TOKENS * ROUNDS * 2);
+}
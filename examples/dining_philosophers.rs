@@ -5,7 +5,7 @@ use bevy_ascii_terminal::{
     AutoCamera, Border, Terminal, TerminalBundle, TerminalPlugin, TileFormatter,
 };
 
-use petnat::{NetId, PetriNet, PetriNetPlugin, Place, Token, Trans, W};
+use petnat::prelude::*;
 
 enum DiningPhils {}
 
@@ -72,14 +72,12 @@ fn main() {
         }))
         .add_plugins(TerminalPlugin)
         .insert_resource(ClearColor(Color::BLACK))
-        .add_plugins(PetriNetPlugin::<DiningPhils> {
-            build: |net| {
-                net.add_place::<ForkClean<LEFT>>()
-                    .add_place::<ForkClean<RIGHT>>()
-                    .compose(add_philosopher::<0>)
-                    .compose(add_philosopher::<1>)
-            },
-        })
+        .add_plugins(PetriNetPlugin::<DiningPhils>::new(|net| {
+            net.add_place::<ForkClean<LEFT>>()
+                .add_place::<ForkClean<RIGHT>>()
+                .compose(add_philosopher::<0>)
+                .compose(add_philosopher::<1>)
+        }))
         .add_systems(Startup, spawn_terminal)
         .add_systems(
             PostStartup,
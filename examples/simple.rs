@@ -2,8 +2,7 @@
 
 use bevy::input::common_conditions::input_just_pressed;
 use bevy::prelude::*;
-use petnat::{NetId, Nn, PetriNet, PetriNetPlugin, Place, Pn, Tn, Token, W};
-use std::any::type_name;
+use petnat::prelude::*;
 
 fn main() {
     App::new()
@@ -11,16 +10,14 @@ fn main() {
         // (P0) -\ 1       1
         //        >-> |T0| -> (P2)
         // (P1) -/ 2
-        .add_plugins(PetriNetPlugin::<Nn<0>> {
-            build: |net| {
-                net.add_place::<Pn<0>>()
-                    .add_place::<Pn<1>>()
-                    .add_place::<Pn<2>>()
-                    // T0 requires 1 token in P0 and 2 tokens in P1 to be enabled
-                    // and it will produce 1 token in P2 when fired
-                    .add_trans::<Tn<0>, ((Pn<0>, W<1>), (Pn<1>, W<2>)), (Pn<2>, W<1>)>()
-            },
-        })
+        .add_plugins(PetriNetPlugin::<Nn<0>>::new(|net| {
+            net.add_place::<Pn<0>>()
+                .add_place::<Pn<1>>()
+                .add_place::<Pn<2>>()
+                // T0 requires 1 token in P0 and 2 tokens in P1 to be enabled
+                // and it will produce 1 token in P2 when fired
+                .add_trans::<Tn<0>, ((Pn<0>, W<1>), (Pn<1>, W<2>)), (Pn<2>, W<1>)>()
+        }))
         .add_systems(Startup, spawn_token::<Nn<0>>)
         .add_systems(
             Update,
@@ -46,14 +43,7 @@ fn spawn_token<Net: NetId>(mut commands: Commands, net: Res<PetriNet<Net>>) {
 fn mark<Net: NetId, P: Place<Net>>(net: Res<PetriNet<Net>>, mut tokens: Query<&mut Token<Net>>) {
     for mut token in &mut tokens {
         net.mark::<P>(&mut token, 1);
-        // TODO: better place/trans names
-        let (_, name) = net
-            .place::<P>()
-            .1
-            .name()
-            .rsplit_once(':')
-            .unwrap_or(("", type_name::<P>()));
-        info!("{} marked!", name);
+        info!("{} marked!", net.place::<P>().1.short_name());
     }
 }
 
@@ -75,10 +65,7 @@ fn print_net<Net: NetId>(
 ) {
     for (id, token) in &tokens {
         info!("== TOKEN {:?} STATE ==", id);
-        info!("P0: {}", net.marks::<Pn<0>>(token));
-        info!("P1: {}", net.marks::<Pn<1>>(token));
-        info!("T0 enabled: {}", net.enabled::<Tn<0>>(token));
-        info!("P2: {}", net.marks::<Pn<2>>(token));
+        info!("{}", net.display_marking(token));
         info!("=====================");
     }
 }
@@ -4,13 +4,32 @@
 #![warn(clippy::pedantic)]
 #![allow(clippy::module_name_repetitions)]
 
+pub use crate::net::compiled::CompiledNet;
 pub use crate::net::place::{Place, PlaceId, PlaceMetadata, Pn};
-pub use crate::net::trans::{Tn, Trans, TransId, TransMetadata};
-pub use crate::net::{Arcs, NetId, Nn, PetriNet, W};
-pub use crate::plugin::PetriNetPlugin;
-pub use net::token::Token;
+pub use crate::net::trans::{Tn, Trans, TransHandle, TransId, TransMetadata};
+pub use crate::net::{
+    Arcs, ColoredFireError, DiffError, Direction, FireError, Marks, NetId, NetWarning, Nn,
+    NotEnabled, NotEnoughMarks, Overflow, PetriNet, Unbounded, W,
+};
+#[cfg(feature = "bevy")]
+pub use crate::plugin::{
+    NetLabel, PetriNetAppExt, PetriNetCommandsExt, PetriNetPlugin, PetriNetSet, PetriNets,
+};
+pub use net::token::{ColoredToken, Token};
+
+pub mod prelude {
+    //! Commonly used items, to be imported with `use petnat::prelude::*;`.
+
+    #[cfg(feature = "bevy")]
+    pub use crate::plugin::{PetriNetCommandsExt, PetriNetPlugin};
+    pub use crate::{
+        NetId, Nn, PetriNet, Place, PlaceId, PlaceMetadata, Pn, Tn, Token, Trans, TransId,
+        TransMetadata, W,
+    };
+}
 
 mod net;
+#[cfg(feature = "bevy")]
 mod plugin;
 
 #[cfg(test)]
@@ -1,52 +1,222 @@
 //! Petri net.
 
-use bevy_ecs::system::Resource;
-use bevy_utils::{all_tuples, thiserror::Error};
+use bevy_utils::{all_tuples, thiserror::Error, StableHashMap};
 use educe::Educe;
+use fixedbitset::FixedBitSet;
+#[cfg(feature = "rand")]
+use rand::Rng;
+#[cfg(feature = "rayon")]
+use rayon::iter::ParallelIterator;
+use std::any::type_name;
 use std::borrow::Cow;
+use std::collections::hash_map::DefaultHasher;
+use std::fmt::{self, Debug, Display};
+use std::hash::{Hash, Hasher};
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Div, Sub, SubAssign};
+use std::sync::Arc;
 
 use place::{Place, PlaceId, PlaceMetadata, Places};
-use token::Token;
-use trans::{Flows, Inflow, Outflow, Trans, TransId, TransMetadata, Transitions};
+use token::{ColoredToken, Token};
+use trans::{Flows, Inflow, Outflow, Trans, TransHandle, TransId, TransMetadata, Transitions};
 
+mod analysis;
+pub mod compiled;
 pub mod place;
+#[cfg(feature = "serde")]
+mod serialize;
 pub mod token;
 pub mod trans;
 
+/// Returns a type name without its module path, keeping its generic arguments.
+fn short_type_name(name: &str) -> &str {
+    let end = name.find('<').unwrap_or(name.len());
+    let start = name[..end].rfind("::").map_or(0, |i| i + 2);
+    &name[start..]
+}
+
 /// Label for a Petri net.
 pub trait NetId: Send + Sync + 'static {}
 
 /// Numbered [`NetId`] for convenience.
+#[cfg_attr(feature = "bevy", derive(bevy_reflect::TypePath))]
 pub enum Nn<const N: usize> {}
 
 impl<const N: usize> NetId for Nn<N> {}
 
 /// Error signifying that the transition was not enabled.
 #[derive(Error, Educe)]
-#[educe(Debug)]
+#[educe(Copy, Clone, PartialEq, Eq, Debug)]
 #[error("Transition {0:?} is not enabled.")]
 pub struct NotEnabled<Net: NetId>(pub TransId<Net>);
 
+impl<Net: NetId> NotEnabled<Net> {
+    /// Returns the transition that was not enabled.
+    #[must_use]
+    pub fn trans(&self) -> TransId<Net> {
+        self.0
+    }
+}
+
 /// Error signifying that the place did not have enough tokens to be unmarked.
 #[derive(Error, Educe)]
-#[educe(Debug)]
+#[educe(Copy, Clone, PartialEq, Eq, Debug)]
 #[error("Place {0:?} does not have enough marks.")]
 pub struct NotEnoughMarks<Net: NetId>(pub PlaceId<Net>);
 
+impl<Net: NetId> NotEnoughMarks<Net> {
+    /// Returns the place that did not have enough marks.
+    #[must_use]
+    pub fn place(&self) -> PlaceId<Net> {
+        self.0
+    }
+}
+
+/// Error signifying that marking the place would overflow its number of marks.
+#[derive(Error, Educe)]
+#[educe(Copy, Clone, PartialEq, Eq, Debug)]
+#[error("Place {0:?} would overflow.")]
+pub struct Overflow<Net: NetId>(pub PlaceId<Net>);
+
+impl<Net: NetId> Overflow<Net> {
+    /// Returns the place that would overflow.
+    #[must_use]
+    pub fn place(&self) -> PlaceId<Net> {
+        self.0
+    }
+}
+
+/// Error signifying that a net has more reachable markings than the exploration limit.
+#[derive(Error, Copy, Clone, PartialEq, Eq, Debug)]
+#[error("More than {0:?} markings are reachable.")]
+pub struct Unbounded(pub usize);
+
+/// Error signifying that a transition could not be fired.
+#[derive(Error, Educe)]
+#[educe(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum FireError<Net: NetId> {
+    /// The transition is not enabled.
+    #[error("Transition {0:?} is not enabled.")]
+    NotEnabled(TransId<Net>),
+    /// Marking the output place would exceed its capacity.
+    #[error("Place {0:?} would exceed its capacity.")]
+    CapacityExceeded(PlaceId<Net>),
+    /// Marking the output place would overflow its number of marks.
+    #[error("Place {0:?} would overflow.")]
+    Overflow(PlaceId<Net>),
+}
+
+/// Error signifying that a difference could not be applied to a token.
+#[derive(Error, Educe)]
+#[educe(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum DiffError<Net: NetId> {
+    /// The place is out of range for the token.
+    #[error("Place {0:?} is out of range for the token.")]
+    OutOfRange(PlaceId<Net>),
+    /// The place does not have enough marks to be unmarked.
+    #[error("Place {0:?} does not have enough marks.")]
+    NotEnoughMarks(PlaceId<Net>),
+    /// Marking the place would overflow its number of marks.
+    #[error("Place {0:?} would overflow.")]
+    Overflow(PlaceId<Net>),
+}
+
+/// Error signifying that a transition could not be fired by a colored token.
+#[derive(Error, Educe)]
+#[educe(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ColoredFireError<Net: NetId> {
+    /// The transition is not enabled.
+    #[error("Transition {0:?} is not enabled.")]
+    NotEnabled(TransId<Net>),
+    /// The transition consumes a different number of values than it produces.
+    #[error("Transition {0:?} does not produce as many values as it consumes.")]
+    Unbalanced(TransId<Net>),
+}
+
+impl<Net: NetId> From<NotEnoughMarks<Net>> for DiffError<Net> {
+    fn from(NotEnoughMarks(place): NotEnoughMarks<Net>) -> Self {
+        Self::NotEnoughMarks(place)
+    }
+}
+
+impl<Net: NetId> From<Overflow<Net>> for DiffError<Net> {
+    fn from(Overflow(place): Overflow<Net>) -> Self {
+        Self::Overflow(place)
+    }
+}
+
+impl<Net: NetId> From<NotEnabled<Net>> for FireError<Net> {
+    fn from(NotEnabled(trans): NotEnabled<Net>) -> Self {
+        Self::NotEnabled(trans)
+    }
+}
+
+impl<Net: NetId> From<NotEnabled<Net>> for ColoredFireError<Net> {
+    fn from(NotEnabled(trans): NotEnabled<Net>) -> Self {
+        Self::NotEnabled(trans)
+    }
+}
+
+impl<Net: NetId> From<Overflow<Net>> for FireError<Net> {
+    fn from(Overflow(place): Overflow<Net>) -> Self {
+        Self::Overflow(place)
+    }
+}
+
+/// Modeling issue found by [`PetriNet::validate`].
+#[derive(Error, Educe)]
+#[educe(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum NetWarning<Net: NetId> {
+    /// The place is not connected to any transition.
+    #[error("Place {0:?} is not connected to any transition.")]
+    IsolatedPlace(PlaceId<Net>),
+    /// The transition has neither inflows nor outflows.
+    #[error("Transition {0:?} has neither inflows nor outflows.")]
+    IsolatedTrans(TransId<Net>),
+    /// The arc between the transition and the place has zero weight.
+    #[error("Arc between transition {0:?} and place {1:?} has zero weight.")]
+    ZeroWeight(TransId<Net>, PlaceId<Net>),
+    /// The transition has an arc to a place that does not belong to the net.
+    #[error("Transition {0:?} references place {1:?}, which is out of range.")]
+    PlaceOutOfRange(TransId<Net>, PlaceId<Net>),
+}
+
+/// Direction of an arc relative to its transition.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Direction {
+    /// Arc from a place to the transition.
+    Input,
+    /// Arc from the transition to a place.
+    Output,
+}
+
 /// Petri net.
 ///
+/// `M` is the numeric type of the arc weights and of the markings of the net's tokens.
+///
 /// TODO:
 ///  - special cases of PNs at the type level?
 ///  - deadlock detection / other useful algorithms
-#[derive(Resource, Educe)]
-#[educe(Debug, Default)]
-pub struct PetriNet<Net: NetId> {
+#[derive(Educe)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::system::Resource))]
+#[educe(Clone, Debug, Default)]
+pub struct PetriNet<Net: NetId, M: Marks = usize> {
     places: Places<Net>,
     transitions: Transitions<Net>,
-    flows: Flows<Net>,
+    flows: Flows<Net, M>,
+    guards: StableHashMap<TransId<Net>, Guard<Net, M>>,
+    capacities: StableHashMap<PlaceId<Net>, M>,
+    #[educe(Debug(ignore))]
+    observers: Vec<Observer<Net, M>>,
 }
 
-impl<Net: NetId> PetriNet<Net> {
+/// Predicate that must hold for a transition to be enabled, in addition to its inflows.
+type Guard<Net, M> = fn(&Token<Net, M>) -> bool;
+
+/// Callback invoked after a transition has been fired.
+type Observer<Net, M> = Arc<dyn Fn(TransId<Net>, &Token<Net, M>) + Send + Sync>;
+
+impl<Net: NetId, M: Marks> PetriNet<Net, M> {
     /// Returns an empty Petri net.
     #[must_use]
     pub fn new() -> Self {
@@ -54,15 +224,83 @@ impl<Net: NetId> PetriNet<Net> {
             places: Places::default(),
             transitions: Transitions::default(),
             flows: Flows::default(),
+            guards: StableHashMap::default(),
+            capacities: StableHashMap::default(),
+            observers: Vec::new(),
         }
     }
 
+    /// Registers a callback invoked after each successful firing of a transition,
+    /// with the fired transition and the token it was fired on.
+    ///
+    /// Callbacks run in the order they were registered. They are not invoked
+    /// while analyzing the net, nor for [`ColoredToken`]s.
+    pub fn on_fire(
+        &mut self,
+        callback: impl Fn(TransId<Net>, &Token<Net, M>) + Send + Sync + 'static,
+    ) {
+        self.observers.push(Arc::new(callback));
+    }
+
     /// Spawns new token.
     #[must_use]
-    pub fn spawn_token(&self) -> Token<Net> {
+    pub fn spawn_token(&self) -> Token<Net, M> {
         Token::new(self.places.len())
     }
 
+    /// Spawns new token that only stores the marks of the marked places.
+    ///
+    /// The token behaves exactly like one spawned with [`PetriNet::spawn_token`],
+    /// but uses less memory when only a few of many places are marked.
+    #[cfg(feature = "sparse")]
+    #[must_use]
+    pub fn spawn_sparse_token(&self) -> Token<Net, M> {
+        Token::new_sparse(self.places.len())
+    }
+
+    /// Spawns new token with `total` marks distributed randomly across the places.
+    ///
+    /// Each mark is put in a place chosen uniformly at random among the places
+    /// that are below their capacity, so the marking respects the capacities.
+    /// Without capacities, the marks of the places follow a multinomial distribution.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the places cannot hold `total` marks within their capacities,
+    /// or if the marks of a place do not fit in `M`.
+    #[cfg(feature = "rand")]
+    #[must_use]
+    pub fn random_marking<R: Rng + ?Sized>(&self, rng: &mut R, total: usize) -> Token<Net, M> {
+        let mut counts = vec![0; self.places.len()];
+        // Places that can take another mark.
+        let mut open: Vec<PlaceId<Net>> = self
+            .places
+            .ids()
+            .filter(|&place| self.capacity_by_id(place) != Some(M::default()))
+            .collect();
+        for _ in 0..total {
+            assert!(
+                !open.is_empty(),
+                "Net `{}` cannot hold {total} marks within its capacities.",
+                type_name::<Net>()
+            );
+            let choice = rng.gen_range(0..open.len());
+            let place = open[choice];
+            counts[place.index()] += 1;
+            if self
+                .capacity_by_id(place)
+                .is_some_and(|capacity| counts[place.index()] >= capacity.to_usize())
+            {
+                open.swap_remove(choice);
+            }
+        }
+        let mut token = self.spawn_token();
+        for (index, count) in counts.into_iter().enumerate() {
+            token.mark_by_id(PlaceId::new(index), M::from_usize(count));
+        }
+        token
+    }
+
     /// Returns a reference to the places of this net.
     #[must_use]
     pub fn place<P: Place<Net>>(&self) -> (PlaceId<Net>, &PlaceMetadata<Net>) {
@@ -77,15 +315,135 @@ impl<Net: NetId> PetriNet<Net> {
         (id, self.transitions.metadata(id))
     }
 
+    /// Removes all markings of a token, so that it can be reused.
+    pub fn reset_token(&self, token: &mut Token<Net, M>) {
+        token.clear();
+    }
+
+    /// Returns an iterator over the places of this net.
+    pub fn places(&self) -> impl Iterator<Item = (PlaceId<Net>, &PlaceMetadata<Net>)> {
+        self.places.iter()
+    }
+
+    /// Returns an iterator over the transitions of this net.
+    pub fn transitions(&self) -> impl Iterator<Item = (TransId<Net>, &TransMetadata<Net>)> {
+        self.transitions.iter()
+    }
+
+    /// Returns the places of this net sorted by name.
+    ///
+    /// Unlike [`PetriNet::places`], the order does not depend on the order of registration.
+    #[must_use]
+    pub fn places_sorted(&self) -> Vec<(PlaceId<Net>, &PlaceMetadata<Net>)> {
+        let mut places: Vec<_> = self.places().collect();
+        places.sort_by(|(_, a), (_, b)| a.name().cmp(b.name()));
+        places
+    }
+
+    /// Returns the transitions of this net sorted by name.
+    ///
+    /// Unlike [`PetriNet::transitions`], the order does not depend on the order of registration.
+    #[must_use]
+    pub fn transitions_sorted(&self) -> Vec<(TransId<Net>, &TransMetadata<Net>)> {
+        let mut transitions: Vec<_> = self.transitions().collect();
+        transitions.sort_by(|(_, a), (_, b)| a.name().cmp(b.name()));
+        transitions
+    }
+
+    /// Returns the group a place is tagged with, if any.
+    #[must_use]
+    pub fn group_of(&self, place: PlaceId<Net>) -> Option<&str> {
+        self.places.metadata(place).group()
+    }
+
+    /// Returns an iterator over the places tagged with a group.
+    pub fn places_in_group<'a>(
+        &'a self,
+        group: &'a str,
+    ) -> impl Iterator<Item = PlaceId<Net>> + 'a {
+        self.places()
+            .filter(move |(_, meta)| meta.group() == Some(group))
+            .map(|(place, _)| place)
+    }
+
+    /// Returns an iterator over the transitions that consume from a place.
+    pub fn consumers(&self, place: PlaceId<Net>) -> impl Iterator<Item = TransId<Net>> + '_ {
+        self.transitions.ids().filter(move |&trans| {
+            self.flows
+                .inflows(trans)
+                .iter()
+                .any(|inflow| inflow.source == place)
+        })
+    }
+
+    /// Returns an iterator over the transitions that produce into a place.
+    pub fn producers(&self, place: PlaceId<Net>) -> impl Iterator<Item = TransId<Net>> + '_ {
+        self.transitions.ids().filter(move |&trans| {
+            self.flows
+                .outflows(trans)
+                .iter()
+                .any(|outflow| outflow.target == place)
+        })
+    }
+
+    /// Returns whether a transition has no input places.
+    ///
+    /// A source transition is enabled regardless of the marking, unless a guard prevents it.
+    #[must_use]
+    pub fn is_source(&self, trans: TransId<Net>) -> bool {
+        self.flows.inflows(trans).is_empty()
+    }
+
+    /// Returns whether a transition has no output places.
+    #[must_use]
+    pub fn is_sink(&self, trans: TransId<Net>) -> bool {
+        self.flows.outflows(trans).is_empty()
+    }
+
+    /// Returns an iterator over the transitions without input places.
+    pub fn sources(&self) -> impl Iterator<Item = TransId<Net>> + '_ {
+        self.transitions
+            .ids()
+            .filter(move |&trans| self.is_source(trans))
+    }
+
+    /// Returns an iterator over the transitions without output places.
+    pub fn sinks(&self) -> impl Iterator<Item = TransId<Net>> + '_ {
+        self.transitions
+            .ids()
+            .filter(move |&trans| self.is_sink(trans))
+    }
+
+    /// Returns the direction and the weight of the arc between a transition and a place,
+    /// or `None` if they are not connected.
+    ///
+    /// Input arcs are looked up first, so only the input arc of a self-loop is returned.
+    #[must_use]
+    pub fn arc_weight(&self, trans: TransId<Net>, place: PlaceId<Net>) -> Option<(Direction, M)> {
+        let input = self
+            .flows
+            .inflows(trans)
+            .iter()
+            .find(|inflow| inflow.source == place)
+            .map(|inflow| (Direction::Input, inflow.weight));
+        input.or_else(|| {
+            self.flows
+                .outflows(trans)
+                .iter()
+                .find(|outflow| outflow.target == place)
+                .map(|outflow| (Direction::Output, outflow.weight))
+        })
+    }
+
     /// Returns the number of times a place has been marked by a token.
     #[must_use]
-    pub fn marks<P: Place<Net>>(&self, token: &Token<Net>) -> usize {
+    pub fn marks<P: Place<Net>>(&self, token: &Token<Net, M>) -> M {
         self.marks_by_id(self.places.id::<P>(), token)
     }
 
     /// Returns whether a transition is enabled.
     #[must_use]
-    pub fn enabled<T: Trans<Net>>(&self, token: &Token<Net>) -> bool {
+    pub fn enabled<T: Trans<Net>>(&self, token: &Token<Net, M>) -> bool {
         let trans = self.transitions.id::<T>();
         self.enabled_by_id(trans, token)
     }
@@ -94,18 +452,108 @@ impl<Net: NetId> PetriNet<Net> {
     ///
     /// ## Errors
     ///
-    /// Returns [`NotEnabled`] if the transition is not enabled.
-    pub fn fire<T: Trans<Net>>(&self, token: &mut Token<Net>) -> Result<(), NotEnabled<Net>> {
+    /// Returns a [`FireError`] if the transition is not enabled,
+    /// or if marking an output place would exceed its capacity or overflow `M`.
+    pub fn fire<T: Trans<Net>>(&self, token: &mut Token<Net, M>) -> Result<(), FireError<Net>> {
         let trans = self.transitions.id::<T>();
         self.fire_by_id(trans, token)
     }
 
+    /// Returns a handle to a transition, which skips looking it up when firing it.
+    #[must_use]
+    pub fn handle<T: Trans<Net>>(&self) -> TransHandle<Net> {
+        TransHandle(self.transitions.id::<T>())
+    }
+
+    /// Returns whether the transition of a handle is enabled.
+    #[must_use]
+    pub fn enabled_handle(&self, handle: TransHandle<Net>, token: &Token<Net, M>) -> bool {
+        self.enabled_by_id(handle.id(), token)
+    }
+
+    /// Fires the transition of a handle.
+    ///
+    /// ## Errors
+    ///
+    /// Returns a [`FireError`] if the transition is not enabled,
+    /// or if marking an output place would exceed its capacity or overflow `M`.
+    pub fn fire_handle(
+        &self,
+        handle: TransHandle<Net>,
+        token: &mut Token<Net, M>,
+    ) -> Result<(), FireError<Net>> {
+        self.fire_by_id(handle.id(), token)
+    }
+
+    /// Fires a transition, returning the marks it added to each output place.
+    ///
+    /// ## Errors
+    ///
+    /// Returns a [`FireError`] if the transition is not enabled,
+    /// or if marking an output place would exceed its capacity or overflow `M`.
+    pub fn fire_report<T: Trans<Net>>(
+        &self,
+        token: &mut Token<Net, M>,
+    ) -> Result<Vec<(PlaceId<Net>, M)>, FireError<Net>> {
+        let trans = self.transitions.id::<T>();
+        self.fire_report_by_id(trans, token)
+    }
+
+    /// Fires a transition up to `n` times, stopping early once it can no longer be fired.
+    ///
+    /// Returns the number of times the transition was fired.
+    ///
+    /// ## Errors
+    ///
+    /// Returns a [`FireError`] if `n` is positive and the transition could not be fired even once.
+    pub fn fire_n<T: Trans<Net>>(
+        &self,
+        token: &mut Token<Net, M>,
+        n: usize,
+    ) -> Result<usize, FireError<Net>> {
+        let trans = self.transitions.id::<T>();
+        self.fire_n_by_id(trans, token, n)
+    }
+
+    /// Returns whether firing a transition leaves every marking unchanged.
+    #[must_use]
+    pub fn is_self_loop<T: Trans<Net>>(&self) -> bool {
+        let trans = self.transitions.id::<T>();
+        self.is_self_loop_by_id(trans)
+    }
+
     /// Marks a place with this token `n` times.
-    pub fn mark<P: Place<Net>>(&self, token: &mut Token<Net>, n: usize) {
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the number of marks would overflow `M`.
+    /// See [`PetriNet::mark_checked`] and [`PetriNet::mark_saturating`] for non-panicking variants.
+    pub fn mark<P: Place<Net>>(&self, token: &mut Token<Net, M>, n: M) {
         let place = self.places.id::<P>();
         self.mark_by_id(place, token, n);
     }
 
+    /// Marks a place with this token `n` times.
+    ///
+    /// ## Errors
+    ///
+    /// Returns [`Overflow`] if the number of marks would overflow `M`.
+    /// The token is left unchanged in that case.
+    pub fn mark_checked<P: Place<Net>>(
+        &self,
+        token: &mut Token<Net, M>,
+        n: M,
+    ) -> Result<(), Overflow<Net>> {
+        let place = self.places.id::<P>();
+        self.mark_checked_by_id(place, token, n)
+    }
+
+    /// Marks a place with this token `n` times, stopping at the maximum value of `M`.
+    pub fn mark_saturating<P: Place<Net>>(&self, token: &mut Token<Net, M>, n: M) {
+        let place = self.places.id::<P>();
+        self.mark_saturating_by_id(place, token, n);
+    }
+
     /// Undoes `n` markings of a place by this token.
     ///
     /// ## Errors
@@ -113,8 +561,8 @@ impl<Net: NetId> PetriNet<Net> {
     /// Returns [`NotEnoughMarks`] if the place does not have enough tokens to be unmarked.
     pub fn unmark<P: Place<Net>>(
         &self,
-        token: &mut Token<Net>,
-        n: usize,
+        token: &mut Token<Net, M>,
+        n: M,
     ) -> Result<(), NotEnoughMarks<Net>> {
         let place = self.places.id::<P>();
         self.unmark_by_id(place, token, n)
@@ -122,15 +570,44 @@ impl<Net: NetId> PetriNet<Net> {
 
     /// Returns the number of times a place has been marked by a token.
     #[must_use]
-    pub fn marks_by_id(&self, place: PlaceId<Net>, token: &Token<Net>) -> usize {
+    pub fn marks_by_id(&self, place: PlaceId<Net>, token: &Token<Net, M>) -> M {
         token.marks_by_id(place)
     }
 
     /// Marks a place with this token `n` times.
-    pub fn mark_by_id(&self, place: PlaceId<Net>, token: &mut Token<Net>, n: usize) {
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the number of marks would overflow `M`.
+    /// See [`PetriNet::mark_checked_by_id`] and [`PetriNet::mark_saturating_by_id`]
+    /// for non-panicking variants.
+    ///
+    /// Also panics if the place was added after the token was spawned,
+    /// unless the token was resized with [`Token::resize_for`].
+    pub fn mark_by_id(&self, place: PlaceId<Net>, token: &mut Token<Net, M>, n: M) {
         token.mark_by_id(place, n);
     }
 
+    /// Marks a place with this token `n` times.
+    ///
+    /// ## Errors
+    ///
+    /// Returns [`Overflow`] if the number of marks would overflow `M`.
+    /// The token is left unchanged in that case.
+    pub fn mark_checked_by_id(
+        &self,
+        place: PlaceId<Net>,
+        token: &mut Token<Net, M>,
+        n: M,
+    ) -> Result<(), Overflow<Net>> {
+        token.mark_checked_by_id(place, n)
+    }
+
+    /// Marks a place with this token `n` times, stopping at the maximum value of `M`.
+    pub fn mark_saturating_by_id(&self, place: PlaceId<Net>, token: &mut Token<Net, M>, n: M) {
+        token.mark_saturating_by_id(place, n);
+    }
+
     /// Undoes `n` markings of a place by this token.
     ///
     /// ## Errors
@@ -139,112 +616,963 @@ impl<Net: NetId> PetriNet<Net> {
     pub fn unmark_by_id(
         &self,
         place: PlaceId<Net>,
-        token: &mut Token<Net>,
-        n: usize,
+        token: &mut Token<Net, M>,
+        n: M,
     ) -> Result<(), NotEnoughMarks<Net>> {
         token.unmark_by_id(place, n)
     }
 
-    /// Tries to return an enabled transition.
+    /// Returns whether a transition is enabled.
+    ///
+    /// A transition is enabled if the token marks each input place enough times,
+    /// and the guard of the transition (if any) holds.
     #[must_use]
-    pub fn enabled_by_id(&self, trans: TransId<Net>, token: &Token<Net>) -> bool {
-        self.flows
-            .inflows(trans)
+    pub fn enabled_by_id(&self, trans: TransId<Net>, token: &Token<Net, M>) -> bool {
+        self.enabled_with(trans, self.flows.inflows(trans), token)
+    }
+
+    /// Returns whether a transition with the given inflows is enabled.
+    fn enabled_with(
+        &self,
+        trans: TransId<Net>,
+        inflows: &[Inflow<Net, M>],
+        token: &Token<Net, M>,
+    ) -> bool {
+        inflows
             .iter()
             .all(|&Inflow { source, weight }| token.marks_by_id(source) >= weight)
+            && self.guards.get(&trans).is_none_or(|guard| guard(token))
     }
 
-    /// Fires transition.
-    ///
-    /// ## Errors
+    /// Returns the enabled transitions as a bit set, indexed by [`TransId::index`].
+    #[must_use]
+    pub fn enabled_mask(&self, token: &Token<Net, M>) -> FixedBitSet {
+        let mut mask = FixedBitSet::with_capacity(self.flows.len());
+        for trans in self.transitions.ids() {
+            mask.set(trans.index(), self.enabled_by_id(trans, token));
+        }
+        mask
+    }
+
+    /// Returns whether two transitions could be fired at the same time.
     ///
-    /// Returns [`NotEnabled`] if the transition is not enabled.
-    pub fn fire_by_id(
+    /// Both transitions have to be enabled, and the token has to mark every input place
+    /// enough times to satisfy both of them at once. Transitions in conflict over the marks
+    /// of a shared input place are not concurrently enabled, even if each of them is enabled.
+    #[must_use]
+    pub fn concurrently_enabled(
         &self,
-        trans: TransId<Net>,
-        token: &mut Token<Net>,
-    ) -> Result<(), NotEnabled<Net>> {
+        t0: TransId<Net>,
+        t1: TransId<Net>,
+        token: &Token<Net, M>,
+    ) -> bool {
+        if !self.enabled_by_id(t0, token) || !self.enabled_by_id(t1, token) {
+            return false;
+        }
+        let mut demand = StableHashMap::<PlaceId<Net>, M>::default();
+        let inflows = self.flows.inflows(t0).iter().chain(self.flows.inflows(t1));
+        for &Inflow { source, weight } in inflows {
+            *demand.entry(source).or_default() += weight;
+        }
+        demand
+            .into_iter()
+            .all(|(place, weight)| token.marks_by_id(place) >= weight)
+    }
+
+    /// Returns whether a transition is persistent in the current marking, that is,
+    /// whether it is enabled and stays enabled after firing any other enabled transition.
+    ///
+    /// Each other transition is fired on a copy of the token, without invoking the callbacks.
+    #[must_use]
+    pub fn is_persistent(&self, trans: TransId<Net>, token: &Token<Net, M>) -> bool {
+        if !self.enabled_by_id(trans, token) {
+            return false;
+        }
+        self.transitions
+            .ids()
+            .filter(|&other| other != trans)
+            .all(|other| {
+                let mut next = token.clone();
+                self.fire_silently(other, &mut next).is_err() || self.enabled_by_id(trans, &next)
+            })
+    }
+
+    /// Returns how many times in a row a transition could be fired,
+    /// if no other transition interfered.
+    ///
+    /// This is the smallest number of times each input place holds the weight of its arc.
+    /// Capacities and overflow of the output places are not taken into account,
+    /// and the degree of an enabled transition without inputs is `usize::MAX`.
+    #[must_use]
+    pub fn enabling_degree(&self, trans: TransId<Net>, token: &Token<Net, M>) -> usize
+    where
+        M: Div<Output = M>,
+    {
         if !self.enabled_by_id(trans, token) {
-            return Err(NotEnabled(trans));
+            return 0;
         }
         self.flows
             .inflows(trans)
             .iter()
-            .for_each(|&Inflow { source, weight }| {
-                token
-                    .unmark_by_id(source, weight)
-                    .unwrap_or_else(|_| unreachable!());
-            });
+            .filter(|&&Inflow { weight, .. }| weight != M::default())
+            .map(|&Inflow { source, weight }| (token.marks_by_id(source) / weight).to_usize())
+            .min()
+            .unwrap_or(usize::MAX)
+    }
+
+    /// Returns how many more marks each input place of a transition needs
+    /// for the transition to be enabled.
+    ///
+    /// Only the input places that are short of marks are listed,
+    /// so an empty vector means every input place is marked enough times.
+    #[must_use]
+    pub fn missing_to_enable(
+        &self,
+        trans: TransId<Net>,
+        token: &Token<Net, M>,
+    ) -> Vec<(PlaceId<Net>, M)> {
         self.flows
-            .outflows(trans)
+            .inflows(trans)
             .iter()
-            .for_each(|&Outflow { target, weight }| token.mark_by_id(target, weight));
+            .filter_map(|&Inflow { source, weight }| {
+                let marks = token.marks_by_id(source);
+                (marks < weight).then(|| (source, weight - marks))
+            })
+            .collect()
+    }
+
+    /// Fires transition.
+    ///
+    /// The input places are unmarked before the output places are marked,
+    /// so a place that is both an input and an output (a self-loop)
+    /// must hold enough marks for the transition to be enabled.
+    /// The token is left untouched if the transition cannot be fired.
+    ///
+    /// ## Errors
+    ///
+    /// Returns [`FireError::NotEnabled`] if the transition is not enabled,
+    /// [`FireError::CapacityExceeded`] if marking an output place would exceed its capacity,
+    /// and [`FireError::Overflow`] if marking an output place would overflow `M`.
+    pub fn fire_by_id(
+        &self,
+        trans: TransId<Net>,
+        token: &mut Token<Net, M>,
+    ) -> Result<(), FireError<Net>> {
+        self.fire_with(
+            trans,
+            self.flows.inflows(trans),
+            self.flows.outflows(trans),
+            token,
+        )
+    }
+
+    /// Fires a transition with the given inflows and outflows, then invokes the callbacks.
+    fn fire_with(
+        &self,
+        trans: TransId<Net>,
+        inflows: &[Inflow<Net, M>],
+        outflows: &[Outflow<Net, M>],
+        token: &mut Token<Net, M>,
+    ) -> Result<(), FireError<Net>> {
+        self.fire_arcs(trans, inflows, outflows, token)?;
+        for observer in &self.observers {
+            observer(trans, token);
+        }
         Ok(())
     }
-}
 
-impl<Net: NetId> PetriNet<Net> {
-    /// Adds a [`Place`] to the net.
-    #[must_use]
-    pub fn add_place<P: Place<Net>>(mut self) -> Self {
-        self.places.register::<P>();
-        self
+    /// Fires a transition without invoking the callbacks.
+    fn fire_silently(
+        &self,
+        trans: TransId<Net>,
+        token: &mut Token<Net, M>,
+    ) -> Result<(), FireError<Net>> {
+        self.fire_arcs(
+            trans,
+            self.flows.inflows(trans),
+            self.flows.outflows(trans),
+            token,
+        )
     }
 
-    /// Adds an "anonymous" place to the net (not a Rust type).
+    /// Fires a transition with the given inflows and outflows.
+    fn fire_arcs(
+        &self,
+        trans: TransId<Net>,
+        inflows: &[Inflow<Net, M>],
+        outflows: &[Outflow<Net, M>],
+        token: &mut Token<Net, M>,
+    ) -> Result<(), FireError<Net>> {
+        if !self.enabled_with(trans, inflows, token) {
+            return Err(NotEnabled(trans).into());
+        }
+        self.check_outputs(inflows, outflows, token)?;
+        for &Inflow { source, weight } in inflows {
+            token
+                .unmark_by_id(source, weight)
+                .unwrap_or_else(|_| unreachable!());
+        }
+        for &Outflow { target, weight } in outflows {
+            token.mark_by_id(target, weight);
+        }
+        Ok(())
+    }
+
+    /// Fires the first transition that can be fired, in the order of registration.
     ///
-    /// Returns the identifier to the place.
-    /// The user is responsible for storing the generated [`PlaceId`].
-    #[must_use]
-    pub fn add_place_anon<N: Into<Cow<'static, str>>>(&mut self, name: N) -> PlaceId<Net> {
-        self.places
-            .register_with_meta(PlaceMetadata::new_anon(name))
+    /// Returns the fired transition, or `None` if no transition could be fired.
+    pub fn step(&self, token: &mut Token<Net, M>) -> Option<TransId<Net>> {
+        self.transitions
+            .ids()
+            .find(|&trans| self.fire_by_id(trans, token).is_ok())
     }
 
-    /// Adds a [`Trans`] and its input and output [`Arcs`] to the net.
+    /// Fires the first transition that can be fired, starting from the one at `cursor`
+    /// and wrapping around, then moves the cursor past the fired transition.
     ///
-    /// ## Panics
+    /// Repeated calls with the same cursor give every transition a turn, unlike [`PetriNet::step`].
+    /// Returns the fired transition, or `None` if no transition could be fired.
+    pub fn step_fair(&self, token: &mut Token<Net, M>, cursor: &mut usize) -> Option<TransId<Net>> {
+        let num_transitions = self.flows.len();
+        let start = cursor.checked_rem(num_transitions)?;
+        let fired = self
+            .transitions
+            .ids()
+            .skip(start)
+            .chain(self.transitions.ids().take(start))
+            .find(|&trans| self.fire_by_id(trans, token).is_ok())?;
+        *cursor = (fired.index() + 1) % num_transitions;
+        Some(fired)
+    }
+
+    /// [Steps](PetriNet::step) every token in parallel.
     ///
-    /// Panics if the transition has already been registered with this net,
-    /// or if any input or output place is not registered with the net.
+    /// Stepping a token only reads the net, which is `Sync`,
+    /// so independent tokens can be stepped on different threads.
+    #[cfg(feature = "rayon")]
+    pub fn step_all<'a>(&self, tokens: impl ParallelIterator<Item = &'a mut Token<Net, M>>)
+    where
+        Net: 'a,
+    {
+        tokens.for_each(|token| {
+            self.step(token);
+        });
+    }
+
+    /// Fires a transition up to `n` times, stopping early once it can no longer be fired.
+    ///
+    /// Returns the number of times the transition was fired.
+    ///
+    /// ## Errors
+    ///
+    /// Returns a [`FireError`] if `n` is positive and the transition could not be fired even once.
+    pub fn fire_n_by_id(
+        &self,
+        trans: TransId<Net>,
+        token: &mut Token<Net, M>,
+        n: usize,
+    ) -> Result<usize, FireError<Net>> {
+        for fired in 0..n {
+            if let Err(err) = self.fire_by_id(trans, token) {
+                return if fired == 0 { Err(err) } else { Ok(fired) };
+            }
+        }
+        Ok(n)
+    }
+
+    /// Returns the marking reached by firing a sequence of transitions in order,
+    /// leaving the token untouched.
+    ///
+    /// The callbacks registered with [`PetriNet::on_fire`] are not invoked.
+    ///
+    /// ## Errors
+    ///
+    /// Returns the index in the sequence and the [`FireError`] of the first transition
+    /// that could not be fired.
+    pub fn simulate(
+        &self,
+        token: &Token<Net, M>,
+        sequence: &[TransId<Net>],
+    ) -> Result<Token<Net, M>, (usize, FireError<Net>)> {
+        let mut token = token.clone();
+        for (step, &trans) in sequence.iter().enumerate() {
+            self.fire_silently(trans, &mut token)
+                .map_err(|err| (step, err))?;
+        }
+        Ok(token)
+    }
+
+    /// Fires a transition, returning the marks it added to each output place.
+    ///
+    /// The places are listed in the order of the outflows of the transition.
+    ///
+    /// ## Errors
+    ///
+    /// Returns a [`FireError`] if the transition is not enabled,
+    /// or if marking an output place would exceed its capacity or overflow `M`.
+    pub fn fire_report_by_id(
+        &self,
+        trans: TransId<Net>,
+        token: &mut Token<Net, M>,
+    ) -> Result<Vec<(PlaceId<Net>, M)>, FireError<Net>> {
+        self.fire_by_id(trans, token)?;
+        Ok(self
+            .flows
+            .outflows(trans)
+            .iter()
+            .map(|&Outflow { target, weight }| (target, weight))
+            .collect())
+    }
+
+    /// Checks that firing an enabled transition keeps its output places
+    /// within their capacities and the range of `M`.
+    fn check_outputs(
+        &self,
+        inflows: &[Inflow<Net, M>],
+        outflows: &[Outflow<Net, M>],
+        token: &Token<Net, M>,
+    ) -> Result<(), FireError<Net>> {
+        for &Outflow { target, .. } in outflows {
+            let consumed: M = inflows
+                .iter()
+                .filter(|inflow| inflow.source == target)
+                .map(|inflow| inflow.weight)
+                .sum();
+            let marks = outflows
+                .iter()
+                .filter(|outflow| outflow.target == target)
+                .try_fold(token.marks_by_id(target) - consumed, |marks, outflow| {
+                    marks.checked_add(outflow.weight)
+                })
+                .ok_or(FireError::Overflow(target))?;
+            if self
+                .capacities
+                .get(&target)
+                .is_some_and(|&capacity| marks > capacity)
+            {
+                return Err(FireError::CapacityExceeded(target));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the maximum number of marks a place can hold, if it is bounded.
+    #[must_use]
+    pub fn capacity<P: Place<Net>>(&self) -> Option<M> {
+        self.capacity_by_id(self.places.id::<P>())
+    }
+
+    /// Returns the maximum number of marks a place can hold, if it is bounded.
+    #[must_use]
+    pub fn capacity_by_id(&self, place: PlaceId<Net>) -> Option<M> {
+        self.capacities.get(&place).copied()
+    }
+
+    /// Returns whether firing a transition leaves every marking unchanged.
+    ///
+    /// This is the case when each place is consumed from as many times as it is produced to,
+    /// and the transition has at least one arc. Such a transition still has to be enabled to fire.
+    #[must_use]
+    pub fn is_self_loop_by_id(&self, trans: TransId<Net>) -> bool {
+        let inflows = self.flows.inflows(trans);
+        let outflows = self.flows.outflows(trans);
+        if inflows.is_empty() && outflows.is_empty() {
+            return false;
+        }
+        let mut effect = StableHashMap::<PlaceId<Net>, (M, M)>::default();
+        for &Inflow { source, weight } in inflows {
+            effect.entry(source).or_default().0 += weight;
+        }
+        for &Outflow { target, weight } in outflows {
+            effect.entry(target).or_default().1 += weight;
+        }
+        effect
+            .values()
+            .all(|(consumed, produced)| consumed == produced)
+    }
+
+    /// Returns a printable summary of the current state of a token.
+    ///
+    /// Lists the number of marks of every marked place,
+    /// followed by the transitions enabled by the token.
+    #[must_use]
+    pub fn display_marking<'a>(&'a self, token: &'a Token<Net, M>) -> impl Display + 'a {
+        MarkingDisplay { net: self, token }
+    }
+
+    /// Draws the net as text, with the marks of the token shown as `*`.
+    ///
+    /// Each transition is drawn on its own line, between its input and output places,
+    /// for example `(P0 *) + (P1)x2 --> [T0] --> (P2)`. Places are drawn as `(name)`,
+    /// transitions as `[name]`, and arcs with a weight other than 1 are suffixed with it.
+    /// Places that are not connected to any transition are drawn on their own lines.
+    #[must_use]
+    pub fn to_ascii(&self, token: &Token<Net, M>) -> String {
+        let place = |place: PlaceId<Net>| {
+            let name = self.places.metadata(place).short_name();
+            let marks = self.marks_by_id(place, token);
+            if marks == M::default() {
+                format!("({name})")
+            } else if marks <= M::from_usize(3) {
+                let stars = (1..=3).take_while(|&n| M::from_usize(n) <= marks);
+                format!("({name} {})", stars.map(|_| '*').collect::<String>())
+            } else {
+                format!("({name} *{marks})")
+            }
+        };
+        let arcs = |arcs: &mut dyn Iterator<Item = (PlaceId<Net>, M)>| {
+            arcs.map(|(id, weight)| {
+                if weight == M::from_usize(1) {
+                    place(id)
+                } else {
+                    format!("{}x{weight}", place(id))
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" + ")
+        };
+        let mut connected = vec![false; self.places.len()];
+        let mut lines = Vec::new();
+        for (trans, meta) in self.transitions() {
+            let inflows = self.flows.inflows(trans);
+            let outflows = self.flows.outflows(trans);
+            let mut line = String::new();
+            if !inflows.is_empty() {
+                line += &arcs(&mut inflows.iter().map(|arc| (arc.source, arc.weight)));
+                line += " --> ";
+            }
+            line += "[";
+            line += meta.short_name();
+            line += "]";
+            if !outflows.is_empty() {
+                line += " --> ";
+                line += &arcs(&mut outflows.iter().map(|arc| (arc.target, arc.weight)));
+            }
+            for &Inflow { source, .. } in inflows {
+                connected[source.index()] = true;
+            }
+            for &Outflow { target, .. } in outflows {
+                connected[target.index()] = true;
+            }
+            lines.push(line);
+        }
+        for (id, _) in self.places() {
+            if !connected[id.index()] {
+                lines.push(place(id));
+            }
+        }
+        lines.join("\n")
+    }
+
+    /// Returns a hash of the structure of the net, for caching the results of analyses.
+    ///
+    /// The hash covers the names of the places and transitions, the capacities of the places,
+    /// and the weights of the arcs. It does not depend on the order of registration,
+    /// and ignores `TypeId`s, so it is stable across runs of the same build.
+    #[must_use]
+    pub fn structure_hash(&self) -> u64 {
+        let mut places: Vec<_> = self
+            .places()
+            .map(|(place, meta)| (meta.name(), self.capacity_by_id(place)))
+            .collect();
+        places.sort_unstable();
+        let name = |place: PlaceId<Net>| self.places.metadata(place).name();
+        let mut transitions: Vec<_> = self
+            .transitions()
+            .map(|(trans, meta)| {
+                let mut inflows: Vec<_> = self
+                    .flows
+                    .inflows(trans)
+                    .iter()
+                    .map(|&Inflow { source, weight }| (name(source), weight))
+                    .collect();
+                let mut outflows: Vec<_> = self
+                    .flows
+                    .outflows(trans)
+                    .iter()
+                    .map(|&Outflow { target, weight }| (name(target), weight))
+                    .collect();
+                inflows.sort_unstable();
+                outflows.sort_unstable();
+                (meta.name(), inflows, outflows)
+            })
+            .collect();
+        transitions.sort_unstable();
+        let mut hasher = DefaultHasher::new();
+        places.hash(&mut hasher);
+        transitions.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Returns whether two nets have the same arcs, ignoring the names of the nodes.
+    ///
+    /// Places are matched by index, while transitions may have been registered in any order:
+    /// the nets are equal if their incidence columns, sorted, are equal.
+    /// Capacities and guards are not compared.
+    #[must_use]
+    pub fn structurally_eq(&self, other: &PetriNet<Net, M>) -> bool {
+        let num_places = self.places.len();
+        if num_places != other.places.len() {
+            return false;
+        }
+        let columns = |net: &PetriNet<Net, M>| {
+            let mut columns: Vec<_> = net
+                .transitions
+                .ids()
+                .map(|trans| {
+                    let mut column = vec![(M::default(), M::default()); num_places];
+                    for &Inflow { source, weight } in net.flows.inflows(trans) {
+                        column[source.index()].0 += weight;
+                    }
+                    for &Outflow { target, weight } in net.flows.outflows(trans) {
+                        column[target.index()].1 += weight;
+                    }
+                    column
+                })
+                .collect();
+            columns.sort_unstable();
+            columns
+        };
+        columns(self) == columns(other)
+    }
+
+    /// Checks the net for modeling mistakes.
+    ///
+    /// ## Errors
+    ///
+    /// Returns every [`NetWarning`] found in the net.
+    pub fn validate(&self) -> Result<(), Vec<NetWarning<Net>>> {
+        let mut warnings = Vec::new();
+        let mut connected = vec![false; self.places.len()];
+        for trans in self.transitions.ids() {
+            let inflows = self.flows.inflows(trans);
+            let outflows = self.flows.outflows(trans);
+            if inflows.is_empty() && outflows.is_empty() {
+                warnings.push(NetWarning::IsolatedTrans(trans));
+            }
+            let arcs = inflows
+                .iter()
+                .map(|&Inflow { source, weight }| (source, weight))
+                .chain(
+                    outflows
+                        .iter()
+                        .map(|&Outflow { target, weight }| (target, weight)),
+                );
+            for (place, weight) in arcs {
+                if let Some(connected) = connected.get_mut(place.index()) {
+                    *connected = true;
+                } else {
+                    warnings.push(NetWarning::PlaceOutOfRange(trans, place));
+                }
+                if weight == M::default() {
+                    warnings.push(NetWarning::ZeroWeight(trans, place));
+                }
+            }
+        }
+        warnings.extend(
+            self.places
+                .ids()
+                .filter(|place| !connected[place.index()])
+                .map(NetWarning::IsolatedPlace),
+        );
+        if warnings.is_empty() {
+            Ok(())
+        } else {
+            Err(warnings)
+        }
+    }
+
+    /// Spawns new colored token.
+    #[must_use]
+    pub fn spawn_colored_token<C: Send + Sync + 'static>(&self) -> ColoredToken<Net, C> {
+        ColoredToken::new(self.places.len())
+    }
+
+    /// Returns the values marking a place, oldest first.
+    #[must_use]
+    pub fn colored_marks<'a, P: Place<Net>, C: Send + Sync + 'static>(
+        &self,
+        token: &'a ColoredToken<Net, C>,
+    ) -> &'a [C] {
+        self.colored_marks_by_id(self.places.id::<P>(), token)
+    }
+
+    /// Returns whether a transition is enabled by a colored token.
+    #[must_use]
+    pub fn enabled_colored<T: Trans<Net>, C: Send + Sync + 'static>(
+        &self,
+        token: &ColoredToken<Net, C>,
+    ) -> bool {
+        let trans = self.transitions.id::<T>();
+        self.enabled_colored_by_id(trans, token)
+    }
+
+    /// Fires a transition, moving values from the input places to the output places.
+    ///
+    /// See [`PetriNet::fire_colored_by_id`] for how values are distributed.
+    ///
+    /// ## Errors
+    ///
+    /// Returns a [`ColoredFireError`] if the transition is not enabled,
+    /// or if it does not produce as many values as it consumes.
+    pub fn fire_colored<T: Trans<Net>, C: Send + Sync + 'static>(
+        &self,
+        token: &mut ColoredToken<Net, C>,
+    ) -> Result<(), ColoredFireError<Net>> {
+        let trans = self.transitions.id::<T>();
+        self.fire_colored_by_id(trans, token)
+    }
+
+    /// Marks a place with a value.
+    pub fn mark_colored<P: Place<Net>, C: Send + Sync + 'static>(
+        &self,
+        token: &mut ColoredToken<Net, C>,
+        value: C,
+    ) {
+        let place = self.places.id::<P>();
+        self.mark_colored_by_id(place, token, value);
+    }
+
+    /// Returns the values marking a place, oldest first.
+    #[must_use]
+    pub fn colored_marks_by_id<'a, C: Send + Sync + 'static>(
+        &self,
+        place: PlaceId<Net>,
+        token: &'a ColoredToken<Net, C>,
+    ) -> &'a [C] {
+        token.marks_by_id(place)
+    }
+
+    /// Marks a place with a value.
+    pub fn mark_colored_by_id<C: Send + Sync + 'static>(
+        &self,
+        place: PlaceId<Net>,
+        token: &mut ColoredToken<Net, C>,
+        value: C,
+    ) {
+        token.mark_by_id(place, value);
+    }
+
+    /// Returns whether a transition is enabled by a colored token.
+    #[must_use]
+    pub fn enabled_colored_by_id<C: Send + Sync + 'static>(
+        &self,
+        trans: TransId<Net>,
+        token: &ColoredToken<Net, C>,
+    ) -> bool {
+        self.flows
+            .inflows(trans)
+            .iter()
+            .all(|&Inflow { source, weight }| token.marks_by_id(source).len() >= weight.to_usize())
+    }
+
+    /// Fires a transition, moving values from the input places to the output places.
+    ///
+    /// The oldest values of each input place are consumed, in the order of the inflows.
+    /// The consumed values are then handed out to the output places in the order of the outflows.
+    /// Values are never created or discarded, so the transition must produce
+    /// as many values as it consumes.
+    ///
+    /// ## Errors
+    ///
+    /// Returns a [`ColoredFireError`] if the transition is not enabled,
+    /// or if it does not produce as many values as it consumes.
+    /// The token is left untouched in both cases.
+    pub fn fire_colored_by_id<C: Send + Sync + 'static>(
+        &self,
+        trans: TransId<Net>,
+        token: &mut ColoredToken<Net, C>,
+    ) -> Result<(), ColoredFireError<Net>> {
+        let consumed: usize = self
+            .flows
+            .inflows(trans)
+            .iter()
+            .map(|inflow| inflow.weight.to_usize())
+            .sum();
+        let produced: usize = self
+            .flows
+            .outflows(trans)
+            .iter()
+            .map(|outflow| outflow.weight.to_usize())
+            .sum();
+        if consumed != produced {
+            return Err(ColoredFireError::Unbalanced(trans));
+        }
+        if !self.enabled_colored_by_id(trans, token) {
+            return Err(NotEnabled(trans).into());
+        }
+        let mut consumed = Vec::new();
+        for &Inflow { source, weight } in self.flows.inflows(trans) {
+            consumed.extend(
+                token
+                    .unmark_by_id(source, weight.to_usize())
+                    .unwrap_or_else(|_| unreachable!()),
+            );
+        }
+        let mut consumed = consumed.into_iter();
+        for &Outflow { target, weight } in self.flows.outflows(trans) {
+            for _ in 0..weight.to_usize() {
+                token.mark_by_id(target, consumed.next().unwrap_or_else(|| unreachable!()));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<Net: NetId, M: Marks> PetriNet<Net, M> {
+    /// Adds a [`Place`] to the net.
+    #[must_use]
+    pub fn add_place<P: Place<Net>>(mut self) -> Self {
+        self.places.register::<P>();
+        self
+    }
+
+    /// Adds a [`Place`] that can hold at most `capacity` marks to the net.
+    ///
+    /// Transitions that would mark the place beyond its capacity fail to fire
+    /// with [`FireError::CapacityExceeded`]. Marking the place directly is not limited,
+    /// and capacities are not consulted for [`ColoredToken`]s.
+    #[must_use]
+    pub fn add_place_with_capacity<P: Place<Net>>(mut self, capacity: M) -> Self {
+        let place = self.places.register::<P>();
+        self.capacities.insert(place, capacity);
+        self
+    }
+
+    /// Adds a [`Place`] tagged with a group to the net.
+    ///
+    /// Groups have no effect on the behavior of the net,
+    /// and can be used to organize the places of larger models.
+    #[must_use]
+    pub fn add_place_in_group<P: Place<Net>, G: Into<Cow<'static, str>>>(
+        mut self,
+        group: G,
+    ) -> Self {
+        self.places.register_in_group::<P, G>(group);
+        self
+    }
+
+    /// Adds a [`Place`] with a display name to the net.
+    ///
+    /// The name is used instead of the type name in the [`PlaceMetadata`].
+    #[must_use]
+    pub fn add_place_named<P: Place<Net>, N: Into<Cow<'static, str>>>(mut self, name: N) -> Self {
+        self.places.register_named::<P, N>(name);
+        self
+    }
+
+    /// Adds an "anonymous" place to the net (not a Rust type).
+    ///
+    /// Returns the identifier to the place.
+    /// The user is responsible for storing the generated [`PlaceId`].
+    #[must_use]
+    pub fn add_place_anon<N: Into<Cow<'static, str>>>(&mut self, name: N) -> PlaceId<Net> {
+        self.places
+            .register_with_meta(PlaceMetadata::new_anon(name))
+    }
+
+    /// Adds a [`Trans`] and its input and output [`Arcs`] to the net.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the transition has already been registered with this net,
+    /// if any input or output place is not registered with the net,
+    /// or if any weight is zero or does not fit in `M`.
     #[must_use]
     pub fn add_trans<T: Trans<Net>, Inflows: Arcs<Net>, Outflows: Arcs<Net>>(mut self) -> Self {
         self.transitions.register::<T>();
+        self.add_arcs::<Inflows, Outflows>()
+    }
+
+    /// Adds a [`Trans`] with a display name and its input and output [`Arcs`] to the net.
+    ///
+    /// The name is used instead of the type name in the [`TransMetadata`].
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the transition has already been registered with this net,
+    /// if any input or output place is not registered with the net,
+    /// or if any weight is zero or does not fit in `M`.
+    #[must_use]
+    pub fn add_trans_named<
+        T: Trans<Net>,
+        Inflows: Arcs<Net>,
+        Outflows: Arcs<Net>,
+        N: Into<Cow<'static, str>>,
+    >(
+        mut self,
+        name: N,
+    ) -> Self {
+        self.transitions.register_named::<T, N>(name);
+        self.add_arcs::<Inflows, Outflows>()
+    }
+
+    /// Adds a [`Trans`] guarded by a predicate and its input and output [`Arcs`] to the net.
+    ///
+    /// The transition is only enabled while the `guard` holds for the token,
+    /// which allows depending on state that is not captured by the marking.
+    /// Guards are not consulted for [`ColoredToken`]s.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the transition has already been registered with this net,
+    /// if any input or output place is not registered with the net,
+    /// or if any weight is zero or does not fit in `M`.
+    #[must_use]
+    pub fn add_trans_guarded<T: Trans<Net>, Inflows: Arcs<Net>, Outflows: Arcs<Net>>(
+        mut self,
+        guard: fn(&Token<Net, M>) -> bool,
+    ) -> Self {
+        let trans = self.transitions.register::<T>();
+        self.guards.insert(trans, guard);
+        self.add_arcs::<Inflows, Outflows>()
+    }
+
+    /// Adds the flows of the last registered transition.
+    fn add_arcs<Inflows: Arcs<Net>, Outflows: Arcs<Net>>(mut self) -> Self {
         self.flows.add_inflows(
             Inflows::erased()
                 .into_iter()
-                .map(|(source, weight)| Inflow {
-                    source: self.places.id_from_erased(source.type_id()),
-                    weight,
+                .map(|(source, weight)| {
+                    let source = self.places.id_from_erased(source.type_id());
+                    self.assert_nonzero_weight(source, weight);
+                    Inflow {
+                        source,
+                        weight: M::from_usize(weight),
+                    }
                 })
                 .collect(),
         );
         self.flows.add_outflows(
             Outflows::erased()
                 .into_iter()
-                .map(|(target, weight)| Outflow {
-                    target: self.places.id_from_erased(target.type_id()),
-                    weight,
+                .map(|(target, weight)| {
+                    let target = self.places.id_from_erased(target.type_id());
+                    self.assert_nonzero_weight(target, weight);
+                    Outflow {
+                        target,
+                        weight: M::from_usize(weight),
+                    }
                 })
                 .collect(),
         );
         self
     }
 
+    /// Rejects the zero weights of [`W`], which are always a modeling mistake.
+    ///
+    /// Weights given at runtime are reported by [`PetriNet::validate`] instead.
+    fn assert_nonzero_weight(&self, place: PlaceId<Net>, weight: usize) {
+        assert!(
+            weight > 0,
+            "Arc of place `{}` in net `{}` has a weight of zero.",
+            self.places.metadata(place).name(),
+            type_name::<Net>(),
+        );
+    }
+
     /// Adds an "anonymous" transition to the net (not a Rust type).
     ///
     /// Returns the identifier to the transition.
     /// The user is responsible for storing the generated [`TransId`].
+    ///
+    /// ## Panics
+    ///
+    /// Panics if any input or output place is not registered with the net.
     #[must_use]
     pub fn add_trans_anon<N: Into<Cow<'static, str>>>(
         &mut self,
         name: N,
-        inflows: &[(PlaceId<Net>, usize)],
-        outflows: &[(PlaceId<Net>, usize)],
+        inflows: &[(PlaceId<Net>, M)],
+        outflows: &[(PlaceId<Net>, M)],
     ) -> TransId<Net> {
         let trans = self
             .transitions
             .register_with_meta(TransMetadata::new_anon(name));
+        self.add_arcs_dyn(inflows, outflows);
+        trans
+    }
+
+    /// Adds a [`Trans`] to the net, with input and output arcs weighted at runtime.
+    ///
+    /// Unlike [`PetriNet::add_trans`], the weights do not have to be known at compile time.
+    ///
+    /// Returns the identifier to the transition.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the transition has already been registered with this net,
+    /// or if any input or output place is not registered with the net.
+    pub fn add_trans_dyn<T: Trans<Net>>(
+        &mut self,
+        inflows: &[(PlaceId<Net>, M)],
+        outflows: &[(PlaceId<Net>, M)],
+    ) -> TransId<Net> {
+        let trans = self.transitions.register::<T>();
+        self.add_arcs_dyn(inflows, outflows);
+        trans
+    }
+
+    /// Adds an input arc from a place to an already registered transition.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the transition or the place is not registered with this net.
+    pub fn add_inflow(&mut self, trans: TransId<Net>, place: PlaceId<Net>, weight: M) {
+        self.assert_arc_in_range(trans, place);
+        self.flows.push_inflow(
+            trans,
+            Inflow {
+                source: place,
+                weight,
+            },
+        );
+    }
+
+    /// Adds an output arc from an already registered transition to a place.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the transition or the place is not registered with this net.
+    pub fn add_outflow(&mut self, trans: TransId<Net>, place: PlaceId<Net>, weight: M) {
+        self.assert_arc_in_range(trans, place);
+        self.flows.push_outflow(
+            trans,
+            Outflow {
+                target: place,
+                weight,
+            },
+        );
+    }
+
+    fn assert_arc_in_range(&self, trans: TransId<Net>, place: PlaceId<Net>) {
+        assert!(
+            trans.index() < self.flows.len(),
+            "Transition `{:?}` not found in net `{}`. Make sure you register it first.",
+            trans,
+            type_name::<Net>(),
+        );
+        self.assert_place_in_range(place);
+    }
+
+    fn assert_place_in_range(&self, place: PlaceId<Net>) {
+        assert!(
+            place.index() < self.places.len(),
+            "Place `{:?}` not found in net `{}`. Make sure you register it first.",
+            place,
+            type_name::<Net>(),
+        );
+    }
+
+    /// Adds the flows of the last registered transition.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if any input or output place is not registered with the net.
+    fn add_arcs_dyn(&mut self, inflows: &[(PlaceId<Net>, M)], outflows: &[(PlaceId<Net>, M)]) {
+        for &(place, _) in inflows.iter().chain(outflows) {
+            self.assert_place_in_range(place);
+        }
         self.flows.add_inflows(
             inflows
                 .iter()
@@ -257,205 +1585,1456 @@ impl<Net: NetId> PetriNet<Net> {
                 .map(|&(target, weight)| Outflow { target, weight })
                 .collect(),
         );
-        trans
     }
 
-    /// Allows composing Petri net configuration.
-    #[must_use]
-    pub fn compose(self, f: impl FnOnce(Self) -> Self) -> Self {
-        f(self)
+    /// Allows composing Petri net configuration.
+    #[must_use]
+    pub fn compose(self, f: impl FnOnce(Self) -> Self) -> Self {
+        f(self)
+    }
+}
+
+struct MarkingDisplay<'a, Net: NetId, M: Marks> {
+    net: &'a PetriNet<Net, M>,
+    token: &'a Token<Net, M>,
+}
+
+impl<Net: NetId, M: Marks> Display for MarkingDisplay<'_, Net, M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let MarkingDisplay { net, token } = self;
+        for (place, meta) in net.places() {
+            let marks = net.marks_by_id(place, token);
+            if marks != M::default() {
+                writeln!(f, "{}: {}", meta.short_name(), marks)?;
+            }
+        }
+        write!(f, "Enabled:")?;
+        let enabled = net
+            .transitions()
+            .filter(|&(trans, _)| net.enabled_by_id(trans, token));
+        for (i, (_, meta)) in enabled.enumerate() {
+            let sep = if i == 0 { " " } else { ", " };
+            write!(f, "{}{}", sep, meta.short_name())?;
+        }
+        Ok(())
+    }
+}
+
+/// Numeric type of arc weights and markings.
+pub trait Marks:
+    Copy
+    + Ord
+    + Hash
+    + Debug
+    + Display
+    + Default
+    + Send
+    + Sync
+    + 'static
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + AddAssign
+    + SubAssign
+    + Sum
+{
+    /// Converts a `usize` weight, such as the one of a [`W`].
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `n` does not fit in `Self`.
+    fn from_usize(n: usize) -> Self;
+
+    /// Converts to a `usize`, such as the number of values in a place of a [`ColoredToken`].
+    ///
+    /// Returns `usize::MAX` if `self` does not fit in a `usize`.
+    fn to_usize(self) -> usize;
+
+    /// Checked addition. Returns `None` if overflow occurred.
+    fn checked_add(self, rhs: Self) -> Option<Self>;
+
+    /// Saturating addition. Returns the maximum value instead of overflowing.
+    #[must_use]
+    fn saturating_add(self, rhs: Self) -> Self;
+}
+
+macro_rules! impl_marks {
+    ($($t:ty),*) => {
+        $(
+            impl Marks for $t {
+                #[inline]
+                fn from_usize(n: usize) -> Self {
+                    Self::try_from(n).unwrap_or_else(|_| {
+                        panic!("Weight {} does not fit in `{}`.", n, type_name::<Self>())
+                    })
+                }
+
+                #[inline]
+                fn to_usize(self) -> usize {
+                    usize::try_from(self).unwrap_or(usize::MAX)
+                }
+
+                #[inline]
+                fn checked_add(self, rhs: Self) -> Option<Self> {
+                    <$t>::checked_add(self, rhs)
+                }
+
+                #[inline]
+                fn saturating_add(self, rhs: Self) -> Self {
+                    <$t>::saturating_add(self, rhs)
+                }
+            }
+        )*
+    };
+}
+
+impl_marks!(u8, u16, u32, u64, u128, usize);
+
+/// Arc weight.
+pub enum W<const N: usize> {}
+
+/// Weighted place-transition arcs.
+pub trait Arcs<Net: NetId> {
+    /// Returns a vector of erased arcs.
+    fn erased() -> Vec<(PlaceMetadata<Net>, usize)>;
+}
+
+// single place case
+impl<Net, P0, const W0: usize> Arcs<Net> for (P0, W<W0>)
+where
+    Net: NetId,
+    P0: Place<Net>,
+{
+    fn erased() -> Vec<(PlaceMetadata<Net>, usize)> {
+        vec![(PlaceMetadata::new::<P0>(), W0)]
+    }
+}
+
+macro_rules! impl_arcs {
+    ($(($place:ident, $weight:ident)),*) => {
+        #[allow(unused_parens)]
+        impl<Net, $($place, const $weight: usize),*> Arcs<Net> for ($(($place, W<$weight>),)*)
+        where
+            Net: NetId,
+            $($place: Place<Net>),*
+        {
+            fn erased() -> Vec<(PlaceMetadata<Net>, usize)> {
+                vec![$((PlaceMetadata::new::<$place>(), $weight)),*]
+            }
+        }
+    };
+}
+
+all_tuples!(impl_arcs, 0, 15, P, W);
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ColoredFireError, DiffError, Direction, FireError, NetId, NetWarning, NotEnabled,
+        NotEnoughMarks, PetriNet, Place, PlaceId, Pn, Tn, Token, Trans, Unbounded, W,
+    };
+
+    enum Minimal {}
+    enum ProdCons {}
+    enum Star {}
+    enum Ring {}
+    enum Choice {}
+    enum Wide {}
+    enum Anon<const MIXED: bool> {}
+
+    enum P0 {}
+    enum P1 {}
+    enum P2 {}
+    enum P3 {}
+    enum P4 {}
+
+    enum T0 {}
+    enum T1 {}
+
+    impl NetId for Minimal {}
+    impl NetId for ProdCons {}
+    impl NetId for Star {}
+    impl NetId for Ring {}
+    impl NetId for Choice {}
+    impl NetId for Wide {}
+    impl<const MIXED: bool> NetId for Anon<MIXED> {}
+
+    impl<Net: NetId> Place<Net> for P0 {}
+    impl<Net: NetId> Place<Net> for P1 {}
+    impl<Net: NetId> Place<Net> for P2 {}
+    impl<Net: NetId> Place<Net> for P3 {}
+    impl<Net: NetId> Place<Net> for P4 {}
+
+    impl<Net: NetId> Trans<Net> for T0 {}
+    impl<Net: NetId> Trans<Net> for T1 {}
+
+    // (p0) -> |t0| -> (p1)
+    fn minimal() -> PetriNet<Minimal> {
+        PetriNet::new()
+            .add_place::<P0>()
+            .add_place::<P1>()
+            .add_trans::<T0, (P0, W<1>), (P1, W<1>)>()
+    }
+
+    // Transitions with no input places are token sources,
+    // and transitions with no output places are token sinks
+    // |t0| -> (p0) -> |t1|
+    fn producer_consumer() -> PetriNet<ProdCons> {
+        PetriNet::new()
+            .add_place::<P0>()
+            .add_trans::<T0, (), (P0, W<1>)>()
+            .add_trans::<T1, (P0, W<1>), ()>()
+    }
+
+    // (p0) -\            /-> (p2)
+    //        >-> |t0| --<--> (p3)
+    // (p1) -/            \-> (p4)
+    fn weighted_star() -> PetriNet<Star> {
+        PetriNet::new()
+            .add_place::<P0>()
+            .add_place::<P1>()
+            .add_place::<P2>()
+            .add_place::<P3>()
+            .add_place::<P4>()
+            .add_trans::<T0, ((P0, W<1>), (P1, W<2>)), ((P2, W<1>), (P3, W<2>), (P4, W<3>))>()
+    }
+
+    // Two places sending a token back and forth through two transitions in opposite directions:
+    //  /--> |t0| -> (p1)
+    // (p0) <- |t1| <--/
+    fn ring() -> PetriNet<Ring> {
+        PetriNet::new()
+            .add_place::<P0>()
+            .add_place::<P1>()
+            .add_trans::<T0, (P0, W<1>), (P1, W<1>)>()
+            .add_trans::<T1, (P1, W<1>), (P0, W<1>)>()
+    }
+
+    // Two transitions sharing a preset place. When one of them fires, the other ceases to be enabled.
+    // (p0) --> |t0| -\
+    // (p1) -<         >-> (p3)
+    // (p2) --> |t1| -/
+    fn choice() -> PetriNet<Choice> {
+        PetriNet::new()
+            .add_place::<P0>()
+            .add_place::<P1>()
+            .add_place::<P2>()
+            .add_place::<P3>()
+            .add_trans::<T0, ((P0, W<1>), (P1, W<1>)), (P3, W<1>)>()
+            .add_trans::<T1, ((P1, W<1>), (P2, W<1>)), (P3, W<1>)>()
+    }
+
+    #[test]
+    fn test_minimal() {
+        let net = minimal();
+        let mut token = net.spawn_token();
+        net.mark::<P0>(&mut token, 1);
+        assert!(net.fire::<T0>(&mut token).is_ok());
+        assert_eq!(net.marks::<P0>(&token), 0);
+        assert_eq!(net.marks::<P1>(&token), 1);
+    }
+
+    #[test]
+    fn test_on_fire_callbacks_run_in_order() {
+        use std::sync::{Arc, Mutex};
+
+        let mut net = minimal();
+        let (t0, _) = net.trans::<T0>();
+        let fired = Arc::new(Mutex::new(Vec::new()));
+        for i in 0..2 {
+            let fired = fired.clone();
+            net.on_fire(move |trans, token| {
+                fired.lock().unwrap().push((i, trans, token.total_marks()));
+            });
+        }
+        let mut token = net.spawn_token();
+        assert!(net.fire::<T0>(&mut token).is_err());
+        net.mark::<P0>(&mut token, 1);
+        assert!(net.state_space_size(&token, 10).is_ok());
+        assert!(fired.lock().unwrap().is_empty());
+        assert!(net.fire::<T0>(&mut token).is_ok());
+        assert_eq!(*fired.lock().unwrap(), [(0, t0, 1), (1, t0, 1)]);
+    }
+
+    #[test]
+    fn test_errors_are_comparable() {
+        let net = minimal();
+        let (p0, _) = net.place::<P0>();
+        let (t0, _) = net.trans::<T0>();
+        let mut token = net.spawn_token();
+        assert_eq!(
+            net.fire::<T0>(&mut token).unwrap_err(),
+            FireError::from(NotEnabled(t0))
+        );
+        assert_eq!(NotEnabled(t0).trans(), t0);
+        let err = net.unmark::<P0>(&mut token, 1).unwrap_err();
+        assert_eq!(err, NotEnoughMarks(p0));
+        assert_eq!(err.place(), p0);
+    }
+
+    #[test]
+    fn test_fire_blocked_by_capacity() {
+        // (p0) -> |t0| -> (p1) with capacity 1
+        let net = PetriNet::<Minimal>::new()
+            .add_place::<P0>()
+            .add_place_with_capacity::<P1>(1)
+            .add_trans::<T0, (P0, W<1>), (P1, W<1>)>();
+        let (p1, _) = net.place::<P1>();
+        assert_eq!(net.capacity::<P0>(), None);
+        assert_eq!(net.capacity::<P1>(), Some(1));
+        let mut token = net.spawn_token();
+        net.mark::<P0>(&mut token, 2);
+        assert!(net.fire::<T0>(&mut token).is_ok());
+        let before = token.clone();
+        assert_eq!(
+            net.fire::<T0>(&mut token),
+            Err(FireError::CapacityExceeded(p1))
+        );
+        assert_eq!(token, before);
+    }
+
+    #[test]
+    fn test_fire_reports_overflow() {
+        let net = PetriNet::<Minimal, u8>::new()
+            .add_place::<P0>()
+            .add_place::<P1>()
+            .add_trans::<T0, (P0, W<1>), (P1, W<1>)>();
+        let (p1, _) = net.place::<P1>();
+        let mut token = net.spawn_token();
+        net.mark::<P0>(&mut token, 1);
+        net.mark::<P1>(&mut token, u8::MAX);
+        assert_eq!(net.fire::<T0>(&mut token), Err(FireError::Overflow(p1)));
+        assert_eq!(net.marks::<P0>(&token), 1);
+    }
+
+    #[test]
+    fn test_minimal_colored() {
+        let net = minimal();
+        let mut token = net.spawn_colored_token();
+        net.mark_colored::<P0, _>(&mut token, 42);
+        assert!(net.fire_colored::<T0, _>(&mut token).is_ok());
+        assert!(net.colored_marks::<P0, _>(&token).is_empty());
+        assert_eq!(net.colored_marks::<P1, _>(&token), [42]);
+    }
+
+    #[test]
+    fn test_colored_with_wide_marks() {
+        let net = PetriNet::<Wide, u64>::new()
+            .add_place::<P0>()
+            .add_place::<P1>()
+            .add_trans::<T0, (P0, W<2>), (P1, W<2>)>();
+        let mut token = net.spawn_colored_token();
+        net.mark_colored::<P0, _>(&mut token, 'a');
+        assert!(net.fire_colored::<T0, _>(&mut token).is_err());
+        net.mark_colored::<P0, _>(&mut token, 'b');
+        assert!(net.fire_colored::<T0, _>(&mut token).is_ok());
+        assert!(net.colored_marks::<P0, _>(&token).is_empty());
+        assert_eq!(net.colored_marks::<P1, _>(&token), ['a', 'b']);
+    }
+
+    #[test]
+    fn test_unbalanced_colored_fails() {
+        let net = wide();
+        let (t0, _) = net.trans::<T0>();
+        let mut token = net.spawn_colored_token();
+        net.mark_colored::<P0, _>(&mut token, 'a');
+        net.mark_colored::<P0, _>(&mut token, 'b');
+        assert_eq!(
+            net.fire_colored::<T0, _>(&mut token),
+            Err(ColoredFireError::Unbalanced(t0))
+        );
+        assert_eq!(net.colored_marks::<P0, _>(&token), ['a', 'b']);
+        assert!(net.colored_marks::<P1, _>(&token).is_empty());
+    }
+
+    #[test]
+    fn test_producer_consumer() {
+        let net = producer_consumer();
+        let mut token = net.spawn_token();
+        assert!(net.fire::<T0>(&mut token).is_ok());
+        assert_eq!(net.marks::<P0>(&token), 1);
+        assert!(net.fire::<T1>(&mut token).is_ok());
+        assert_eq!(net.marks::<P0>(&token), 0);
+    }
+
+    #[test]
+    fn test_fire_n_stops_when_not_enabled() {
+        let net = producer_consumer();
+        let (t1, _) = net.trans::<T1>();
+        let mut token = net.spawn_token();
+        assert_eq!(
+            net.fire_n::<T1>(&mut token, 10),
+            Err(FireError::NotEnabled(t1))
+        );
+        net.mark::<P0>(&mut token, 3);
+        assert_eq!(net.fire_n::<T1>(&mut token, 10), Ok(3));
+        assert_eq!(net.marks::<P0>(&token), 0);
+        assert_eq!(net.fire_n::<T0>(&mut token, 5), Ok(5));
+        assert_eq!(net.fire_n::<T1>(&mut token, 0), Ok(0));
+        assert_eq!(net.marks::<P0>(&token), 5);
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    fn test_random_marking() {
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        let net = choice();
+        let mut rng = StdRng::seed_from_u64(0);
+        for total in [0, 1, 7, 100] {
+            let token = net.random_marking(&mut rng, total);
+            assert_eq!(token.total_marks(), total);
+            assert_eq!(token.to_marking().len(), net.places().count());
+        }
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    fn test_random_marking_respects_capacities() {
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        let net = PetriNet::<Choice>::new()
+            .add_place_with_capacity::<P0>(2)
+            .add_place_with_capacity::<P1>(0)
+            .add_place::<P2>();
+        let (p0, _) = net.place::<P0>();
+        let (p1, _) = net.place::<P1>();
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..20 {
+            let token = net.random_marking(&mut rng, 10);
+            assert_eq!(token.total_marks(), 10);
+            assert!(token.marks_by_id(p0) <= 2);
+            assert_eq!(token.marks_by_id(p1), 0);
+        }
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    #[should_panic(expected = "cannot hold 4 marks")]
+    fn test_random_marking_beyond_capacities() {
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        let net = PetriNet::<Choice>::new()
+            .add_place_with_capacity::<P0>(1)
+            .add_place_with_capacity::<P1>(2);
+        let _ = net.random_marking(&mut StdRng::seed_from_u64(0), 4);
+    }
+
+    #[test]
+    fn test_fire_report() {
+        let net = weighted_star();
+        let (t0, _) = net.trans::<T0>();
+        let (p2, _) = net.place::<P2>();
+        let (p3, _) = net.place::<P3>();
+        let (p4, _) = net.place::<P4>();
+        let mut token = net.spawn_token();
+        assert_eq!(
+            net.fire_report::<T0>(&mut token),
+            Err(FireError::NotEnabled(t0))
+        );
+        net.mark::<P0>(&mut token, 1);
+        net.mark::<P1>(&mut token, 2);
+        assert_eq!(
+            net.fire_report::<T0>(&mut token),
+            Ok(vec![(p2, 1), (p3, 2), (p4, 3)])
+        );
+    }
+
+    #[test]
+    fn test_is_persistent() {
+        let net = minimal();
+        let (t0, _) = net.trans::<T0>();
+        let mut token = net.spawn_token();
+        assert!(!net.is_persistent(t0, &token));
+        net.mark::<P0>(&mut token, 1);
+        assert!(net.is_persistent(t0, &token));
+
+        let net = choice();
+        let (t0, _) = net.trans::<T0>();
+        let (t1, _) = net.trans::<T1>();
+        let mut token = net.spawn_token();
+        net.mark::<P0>(&mut token, 1);
+        net.mark::<P1>(&mut token, 1);
+        net.mark::<P2>(&mut token, 1);
+        assert!(!net.is_persistent(t0, &token));
+        assert!(!net.is_persistent(t1, &token));
+        net.mark::<P1>(&mut token, 1);
+        assert!(net.is_persistent(t0, &token));
+        assert!(net.is_persistent(t1, &token));
+    }
+
+    #[test]
+    fn test_structurally_eq() {
+        let net = choice();
+        let reordered = PetriNet::<Choice>::new()
+            .add_place::<P0>()
+            .add_place::<P1>()
+            .add_place::<P2>()
+            .add_place::<P3>()
+            .add_trans::<T1, ((P2, W<1>), (P1, W<1>)), (P3, W<1>)>()
+            .add_trans::<T0, ((P1, W<1>), (P0, W<1>)), (P3, W<1>)>();
+        assert!(net.structurally_eq(&reordered));
+        let reweighted = PetriNet::<Choice>::new()
+            .add_place::<P0>()
+            .add_place::<P1>()
+            .add_place::<P2>()
+            .add_place::<P3>()
+            .add_trans::<T0, ((P0, W<1>), (P1, W<1>)), (P3, W<2>)>()
+            .add_trans::<T1, ((P1, W<1>), (P2, W<1>)), (P3, W<1>)>();
+        assert!(!net.structurally_eq(&reweighted));
+        assert!(!net.structurally_eq(&PetriNet::new()));
+    }
+
+    #[test]
+    fn test_sources_and_sinks() {
+        let net = producer_consumer();
+        let (t0, _) = net.trans::<T0>();
+        let (t1, _) = net.trans::<T1>();
+        assert!(net.is_source(t0) && !net.is_sink(t0));
+        assert!(net.is_sink(t1) && !net.is_source(t1));
+        assert_eq!(net.sources().collect::<Vec<_>>(), [t0]);
+        assert_eq!(net.sinks().collect::<Vec<_>>(), [t1]);
+        let net = ring();
+        assert_eq!(net.sources().count(), 0);
+        assert_eq!(net.sinks().count(), 0);
+    }
+
+    #[test]
+    fn test_arc_weight() {
+        let net = weighted_star();
+        let (t0, _) = net.trans::<T0>();
+        let (p1, _) = net.place::<P1>();
+        let (p4, _) = net.place::<P4>();
+        assert_eq!(net.arc_weight(t0, p1), Some((Direction::Input, 2)));
+        assert_eq!(net.arc_weight(t0, p4), Some((Direction::Output, 3)));
+        let net = choice();
+        let (t0, _) = net.trans::<T0>();
+        let (p2, _) = net.place::<P2>();
+        assert_eq!(net.arc_weight(t0, p2), None);
+    }
+
+    #[test]
+    fn test_simulate() {
+        let net = ring();
+        let (t0, _) = net.trans::<T0>();
+        let (t1, _) = net.trans::<T1>();
+        let mut token = net.spawn_token();
+        net.mark::<P0>(&mut token, 1);
+        let reached = net.simulate(&token, &[t0, t1, t0]).unwrap();
+        assert_eq!(net.marks::<P0>(&reached), 0);
+        assert_eq!(net.marks::<P1>(&reached), 1);
+        assert_eq!(net.marks::<P0>(&token), 1);
+        assert_eq!(
+            net.simulate(&token, &[t0, t0, t1]),
+            Err((1, FireError::NotEnabled(t0)))
+        );
+    }
+
+    #[test]
+    fn test_enabling_degree() {
+        let net = weighted_star();
+        let (t0, _) = net.trans::<T0>();
+        let mut token = net.spawn_token();
+        assert_eq!(net.enabling_degree(t0, &token), 0);
+        net.mark::<P0>(&mut token, 5);
+        net.mark::<P1>(&mut token, 3);
+        assert_eq!(net.enabling_degree(t0, &token), 1);
+        net.mark::<P1>(&mut token, 5);
+        assert_eq!(net.enabling_degree(t0, &token), 4);
+
+        let net = producer_consumer();
+        let (t0, _) = net.trans::<T0>();
+        assert_eq!(net.enabling_degree(t0, &net.spawn_token()), usize::MAX);
+    }
+
+    #[test]
+    fn test_to_ascii() {
+        let net = minimal();
+        let mut token = net.spawn_token();
+        assert_eq!(net.to_ascii(&token), "(P0) --> [T0] --> (P1)");
+        net.mark::<P0>(&mut token, 2);
+        assert_eq!(net.to_ascii(&token), "(P0 **) --> [T0] --> (P1)");
+
+        let net = weighted_star();
+        let mut token = net.spawn_token();
+        net.mark::<P3>(&mut token, 4);
+        assert_eq!(
+            net.to_ascii(&token),
+            "(P0) + (P1)x2 --> [T0] --> (P2) + (P3 *4)x2 + (P4)x3"
+        );
+    }
+
+    #[cfg(feature = "sparse")]
+    #[test]
+    fn test_sparse_token_behaves_like_dense_token() {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{Hash, Hasher};
+
+        let net = weighted_star();
+        let mut dense = net.spawn_token();
+        let mut sparse = net.spawn_sparse_token();
+        for token in [&mut dense, &mut sparse] {
+            net.mark::<P0>(token, 2);
+            net.mark::<P1>(token, 3);
+            assert!(net.fire::<T0>(token).is_ok());
+            assert!(net.fire::<T0>(token).is_err());
+            assert_eq!(token.total_marks(), 1 + 1 + 1 + 2 + 3);
+        }
+        assert_eq!(dense, sparse);
+        assert_eq!(dense.as_slice(), Some(&*dense.to_marking()));
+        assert_eq!(sparse.as_slice(), None);
+        assert_eq!(dense.to_marking(), sparse.to_marking());
+        assert_eq!(dense.diff(&sparse), []);
+        assert_eq!(
+            net.display_marking(&dense).to_string(),
+            net.display_marking(&sparse).to_string()
+        );
+        let hash = |token: &crate::Token<Star>| {
+            let mut hasher = DefaultHasher::new();
+            token.hash(&mut hasher);
+            hasher.finish()
+        };
+        assert_eq!(hash(&dense), hash(&sparse));
+        dense.clear();
+        sparse.clear();
+        assert_eq!(dense, sparse);
+    }
+
+    #[test]
+    fn test_step_fires_first_enabled_transition() {
+        let net = ring();
+        let (t0, _) = net.trans::<T0>();
+        let (t1, _) = net.trans::<T1>();
+        let mut token = net.spawn_token();
+        assert_eq!(net.step(&mut token), None);
+        net.mark::<P1>(&mut token, 1);
+        assert_eq!(net.step(&mut token), Some(t1));
+        assert_eq!(net.step(&mut token), Some(t0));
+        assert_eq!(net.marks::<P1>(&token), 1);
+    }
+
+    #[test]
+    fn test_step_fair_takes_turns() {
+        let net = producer_consumer();
+        let (t0, _) = net.trans::<T0>();
+        let (t1, _) = net.trans::<T1>();
+        let mut token = net.spawn_token();
+        net.mark::<P0>(&mut token, 2);
+        let mut cursor = 0;
+        for _ in 0..3 {
+            assert_eq!(net.step_fair(&mut token, &mut cursor), Some(t0));
+            assert_eq!(net.step_fair(&mut token, &mut cursor), Some(t1));
+        }
+        assert_eq!(net.marks::<P0>(&token), 2);
+
+        let net = PetriNet::<Minimal>::new();
+        let mut token = net.spawn_token();
+        assert_eq!(net.step_fair(&mut token, &mut cursor), None);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_step_all_matches_sequential_step() {
+        use rayon::iter::IntoParallelRefMutIterator;
+
+        let net = choice();
+        let mut tokens: Vec<_> = (0..64)
+            .map(|i| {
+                let mut token = net.spawn_token();
+                net.mark::<P0>(&mut token, i % 2);
+                net.mark::<P1>(&mut token, i % 3);
+                net.mark::<P2>(&mut token, i % 5);
+                token
+            })
+            .collect();
+        let mut expected = tokens.clone();
+        for token in &mut expected {
+            net.step(token);
+        }
+        net.step_all(tokens.par_iter_mut());
+        assert_eq!(tokens, expected);
+    }
+
+    #[test]
+    fn test_compiled_net_fires_like_dynamic_net() {
+        let net = weighted_star();
+        let mut expected = net.spawn_token();
+        let net = net.compile();
+        let mut token = net.spawn_token();
+        for token in [&mut expected, &mut token] {
+            net.mark::<P0>(token, 2);
+            net.mark::<P1>(token, 3);
+        }
+        for _ in 0..3 {
+            assert_eq!(
+                net.fire::<T0>(&mut token),
+                PetriNet::fire::<T0>(&net, &mut expected)
+            );
+            assert_eq!(token, expected);
+        }
+        assert!(!net.enabled::<T0>(&token));
+    }
+
+    #[test]
+    fn test_compiled_net_fails_like_dynamic_net() {
+        fn build() -> PetriNet<Star> {
+            PetriNet::new()
+                .add_place_with_capacity::<P0>(4)
+                .add_place_with_capacity::<P1>(3)
+                .add_place::<P2>()
+                .add_trans::<T0, (P0, W<1>), ((P0, W<1>), (P1, W<2>))>()
+                .add_trans_guarded::<T1, (P1, W<1>), (P2, W<1>)>(|token| token.total_marks() < 6)
+                .add_trans::<Tn<2>, (P2, W<1>), (P0, W<2>)>()
+        }
+        let net = build();
+        let compiled = build().compile();
+        let mut expected = net.spawn_token();
+        net.mark::<P0>(&mut expected, 1);
+        let mut token = expected.clone();
+        let mut steps = 0;
+        loop {
+            assert_eq!(compiled.enabled_mask(&token), net.enabled_mask(&expected));
+            for trans in net.transitions.ids() {
+                let (mut compiled_next, mut dynamic_next) = (token.clone(), expected.clone());
+                assert_eq!(
+                    compiled.fire_by_id(trans, &mut compiled_next),
+                    net.fire_by_id(trans, &mut dynamic_next)
+                );
+                assert_eq!(compiled_next, dynamic_next);
+            }
+            let fired = compiled.step(&mut token);
+            assert_eq!(fired, net.step(&mut expected));
+            assert_eq!(token, expected);
+            if fired.is_none() {
+                break;
+            }
+            steps += 1;
+        }
+        assert_eq!(steps, 7);
+    }
+
+    #[test]
+    fn test_fire_handle_matches_fire() {
+        let net = producer_consumer();
+        let produce = net.handle::<T0>();
+        let consume = net.handle::<T1>();
+        assert_eq!(produce.id(), net.trans::<T0>().0);
+        let mut token = net.spawn_token();
+        let mut expected = net.spawn_token();
+        assert!(!net.enabled_handle(consume, &token));
+        assert_eq!(
+            net.fire_handle(consume, &mut token),
+            net.fire::<T1>(&mut expected)
+        );
+        for _ in 0..3 {
+            assert!(net.fire_handle(produce, &mut token).is_ok());
+            assert!(net.fire::<T0>(&mut expected).is_ok());
+        }
+        assert!(net.enabled_handle(consume, &token));
+        assert!(net.fire_handle(consume, &mut token).is_ok());
+        assert!(net.fire::<T1>(&mut expected).is_ok());
+        assert_eq!(token, expected);
+    }
+
+    #[test]
+    fn test_enabled_mask() {
+        let net = choice();
+        let mut token = net.spawn_token();
+        let agrees = |token: &Token<Choice>| {
+            let mask = net.enabled_mask(token);
+            assert_eq!(mask.len(), net.transitions().count());
+            net.transitions()
+                .all(|(trans, _)| mask[trans.index()] == net.enabled_by_id(trans, token))
+        };
+        assert!(agrees(&token));
+        net.mark::<P0>(&mut token, 1);
+        net.mark::<P1>(&mut token, 1);
+        assert!(agrees(&token));
+        assert_eq!(net.enabled_mask(&token).ones().collect::<Vec<_>>(), [0]);
+        net.mark::<P2>(&mut token, 1);
+        assert!(agrees(&token));
+        assert_eq!(net.enabled_mask(&token).count_ones(..), 2);
+    }
+
+    #[test]
+    fn test_concurrently_enabled() {
+        let net = choice();
+        let (t0, _) = net.trans::<T0>();
+        let (t1, _) = net.trans::<T1>();
+        let mut token = net.spawn_token();
+        net.mark::<P0>(&mut token, 1);
+        net.mark::<P1>(&mut token, 1);
+        net.mark::<P2>(&mut token, 1);
+        assert!(net.enabled_by_id(t0, &token));
+        assert!(net.enabled_by_id(t1, &token));
+        assert!(!net.concurrently_enabled(t0, t1, &token));
+        net.mark::<P1>(&mut token, 1);
+        assert!(net.concurrently_enabled(t0, t1, &token));
+        assert!(!net.concurrently_enabled(t0, t0, &token));
+    }
+
+    #[test]
+    fn test_weighted_star() {
+        let net = weighted_star();
+        let mut token = net.spawn_token();
+        net.mark::<P0>(&mut token, 1);
+        net.mark::<P1>(&mut token, 2);
+        assert!(net.fire::<T0>(&mut token).is_ok());
+        assert_eq!(net.marks::<P0>(&token), 0);
+        assert_eq!(net.marks::<P1>(&token), 0);
+        assert_eq!(net.marks::<P2>(&token), 1);
+        assert_eq!(net.marks::<P3>(&token), 2);
+        assert_eq!(net.marks::<P4>(&token), 3);
+    }
+
+    #[test]
+    fn test_missing_to_enable() {
+        let net = weighted_star();
+        let (t0, _) = net.trans::<T0>();
+        let (p0, _) = net.place::<P0>();
+        let (p1, _) = net.place::<P1>();
+        let mut token = net.spawn_token();
+        assert_eq!(net.missing_to_enable(t0, &token), [(p0, 1), (p1, 2)]);
+        net.mark::<P0>(&mut token, 1);
+        net.mark::<P1>(&mut token, 1);
+        assert_eq!(net.missing_to_enable(t0, &token), [(p1, 1)]);
+        net.mark::<P1>(&mut token, 1);
+        assert!(net.missing_to_enable(t0, &token).is_empty());
+    }
+
+    #[test]
+    fn test_display_marking() {
+        let net = weighted_star();
+        let mut token = net.spawn_token();
+        net.mark::<P0>(&mut token, 1);
+        net.mark::<P1>(&mut token, 2);
+        assert_eq!(
+            net.display_marking(&token).to_string(),
+            "P0: 1\nP1: 2\nEnabled: T0"
+        );
+        assert!(net.fire::<T0>(&mut token).is_ok());
+        assert_eq!(
+            net.display_marking(&token).to_string(),
+            "P2: 1\nP3: 2\nP4: 3\nEnabled:"
+        );
+    }
+
+    #[test]
+    fn test_diff() {
+        let net = minimal();
+        let (p0, _) = net.place::<P0>();
+        let (p1, _) = net.place::<P1>();
+        let mut before = net.spawn_token();
+        net.mark::<P0>(&mut before, 1);
+        let mut after = before.clone();
+        assert!(net.fire::<T0>(&mut after).is_ok());
+        let diff = after.diff(&before);
+        assert_eq!(diff, [(p0, -1), (p1, 1)]);
+        assert_eq!(before.apply_diff(&diff), Ok(()));
+        assert_eq!(before, after);
+        assert!(after.diff(&before).is_empty());
+    }
+
+    #[test]
+    fn test_failed_apply_diff_leaves_token_untouched() {
+        let net = minimal();
+        let (p0, _) = net.place::<P0>();
+        let (p1, _) = net.place::<P1>();
+        let mut token = net.spawn_token();
+        net.mark::<P0>(&mut token, 1);
+        let before = token.clone();
+        assert!(token.apply_diff(&[(p0, 1), (p1, -1)]).is_err());
+        assert_eq!(token, before);
+    }
+
+    #[test]
+    fn test_apply_diff_reports_bad_diffs() {
+        let net = wide();
+        let (p0, _) = net.place::<P0>();
+        let (p1, _) = net.place::<P1>();
+        let mut token = net.spawn_token();
+        net.mark::<P1>(&mut token, u64::MAX);
+        let before = token.clone();
+        assert_eq!(
+            token.apply_diff(&[(p0, -1)]),
+            Err(DiffError::NotEnoughMarks(p0))
+        );
+        assert_eq!(token.apply_diff(&[(p1, 1)]), Err(DiffError::Overflow(p1)));
+        let foreign = PlaceId::new(2);
+        assert_eq!(
+            token.apply_diff(&[(p0, 1), (foreign, 1)]),
+            Err(DiffError::OutOfRange(foreign))
+        );
+        assert_eq!(token, before);
+    }
+
+    #[test]
+    fn test_ring() {
+        let net = ring();
+        let mut token = net.spawn_token();
+        net.mark::<P0>(&mut token, 1);
+        assert_eq!(net.marks::<P0>(&token), 1);
+        assert_eq!(net.marks::<P1>(&token), 0);
+        assert!(net.fire::<T0>(&mut token).is_ok());
+        assert_eq!(net.marks::<P0>(&token), 0);
+        assert_eq!(net.marks::<P1>(&token), 1);
+        assert!(net.fire::<T1>(&mut token).is_ok());
+        assert_eq!(net.marks::<P0>(&token), 1);
+        assert_eq!(net.marks::<P1>(&token), 0);
+    }
+
+    // Runs with both token storages in CI, with and without the `smallvec` feature.
+    #[test]
+    fn test_ring_firing_matches_vec_marking() {
+        let net = ring();
+        let (t0, _) = net.trans::<T0>();
+        let (t1, _) = net.trans::<T1>();
+        let mut token = net.spawn_token();
+        net.mark::<P0>(&mut token, 3);
+        let mut expected = vec![3, 0];
+        for trans in [t0, t0, t1, t0, t0, t1, t1, t1, t0] {
+            assert!(net.fire_by_id(trans, &mut token).is_ok());
+            let (from, to) = if trans == t0 { (0, 1) } else { (1, 0) };
+            expected[from] -= 1;
+            expected[to] += 1;
+            assert_eq!(token.as_slice(), Some(&*expected));
+        }
+        assert_eq!(expected, [2, 1]);
+    }
+
+    #[test]
+    fn test_ring_reachability() {
+        let net = ring();
+        let mut from = net.spawn_token();
+        net.mark::<P0>(&mut from, 1);
+        let mut target = net.spawn_token();
+        net.mark::<P1>(&mut target, 1);
+        assert!(net.is_reachable(&from, &target, 10));
+        net.reset_token(&mut target);
+        net.mark::<P0>(&mut target, 2);
+        assert!(!net.is_reachable(&from, &target, 10));
+    }
+
+    #[test]
+    fn test_minimal_fire_sequence() {
+        let net = minimal();
+        let (t0, _) = net.trans::<T0>();
+        let mut from = net.spawn_token();
+        net.mark::<P0>(&mut from, 1);
+        let mut target = net.spawn_token();
+        net.mark::<P1>(&mut target, 1);
+        assert_eq!(net.fire_sequence_to(&from, &target, 10), Some(vec![t0]));
+        assert_eq!(net.fire_sequence_to(&from, &from, 10), Some(vec![]));
+        assert_eq!(net.fire_sequence_to(&target, &from, 10), None);
+    }
+
+    #[test]
+    fn test_ring_fire_sequence_is_shortest() {
+        let net = ring();
+        let (t0, _) = net.trans::<T0>();
+        let (t1, _) = net.trans::<T1>();
+        let mut from = net.spawn_token();
+        net.mark::<P0>(&mut from, 2);
+        let mut target = net.spawn_token();
+        net.mark::<P1>(&mut target, 2);
+        assert_eq!(net.fire_sequence_to(&from, &target, 10), Some(vec![t0, t0]));
+        assert_eq!(net.fire_sequence_to(&target, &from, 10), Some(vec![t1, t1]));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let net = choice();
+        let json = serde_json::to_string(&net).unwrap();
+        let loaded: PetriNet<Choice> = serde_json::from_str(&json).unwrap();
+        let names = |net: &PetriNet<Choice>| {
+            let places: Vec<_> = net
+                .places()
+                .map(|(_, meta)| meta.name().to_owned())
+                .collect();
+            let transitions: Vec<_> = net
+                .transitions()
+                .map(|(_, meta)| meta.name().to_owned())
+                .collect();
+            (places, transitions)
+        };
+        assert_eq!(names(&loaded), names(&net));
+        for (trans, _) in net.transitions() {
+            let inflows = |net: &PetriNet<Choice>| {
+                net.flows
+                    .inflows(trans)
+                    .iter()
+                    .map(|inflow| (inflow.source, inflow.weight))
+                    .collect::<Vec<_>>()
+            };
+            let outflows = |net: &PetriNet<Choice>| {
+                net.flows
+                    .outflows(trans)
+                    .iter()
+                    .map(|outflow| (outflow.target, outflow.weight))
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(inflows(&loaded), inflows(&net));
+            assert_eq!(outflows(&loaded), outflows(&net));
+        }
+        assert!(loaded
+            .places()
+            .all(|(_, meta)| meta.get_type_id().is_none()));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip_keeps_short_names() {
+        fn short_names(net: &PetriNet<Choice>) -> Vec<&str> {
+            net.places()
+                .map(|(_, meta)| meta.short_name())
+                .chain(net.transitions().map(|(_, meta)| meta.short_name()))
+                .collect()
+        }
+        let net = choice();
+        let json = serde_json::to_string(&net).unwrap();
+        let loaded: PetriNet<Choice> = serde_json::from_str(&json).unwrap();
+        assert_eq!(short_names(&net), ["P0", "P1", "P2", "P3", "T0", "T1"]);
+        assert_eq!(short_names(&loaded), short_names(&net));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip_is_structurally_eq() {
+        let net = choice();
+        let json = serde_json::to_string(&net).unwrap();
+        let loaded: PetriNet<Choice> = serde_json::from_str(&json).unwrap();
+        assert!(loaded.structurally_eq(&net));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_deserialize_rejects_out_of_range_place() {
+        let json = r#"{"places":[],"transitions":[{"name":"T","typed":false,"inflows":[[0,1]],"outflows":[]}]}"#;
+        assert!(serde_json::from_str::<PetriNet<Choice>>(json).is_err());
+    }
+
+    #[test]
+    fn test_siphons_and_traps() {
+        let net = choice();
+        let [p0, p1, p2, p3] = [
+            net.place::<P0>().0,
+            net.place::<P1>().0,
+            net.place::<P2>().0,
+            net.place::<P3>().0,
+        ];
+        assert_eq!(net.siphons(), [vec![p0], vec![p1], vec![p2]]);
+        assert_eq!(net.traps(), [vec![p3]]);
+
+        let net = ring();
+        let ring = [net.place::<P0>().0, net.place::<P1>().0];
+        assert_eq!(net.siphons(), [ring]);
+        assert_eq!(net.traps(), [ring]);
     }
-}
 
-/// Arc weight.
-pub enum W<const N: usize> {}
+    #[test]
+    fn test_state_space_size() {
+        let net = ring();
+        let mut token = net.spawn_token();
+        net.mark::<P0>(&mut token, 1);
+        assert_eq!(net.state_space_size(&token, 10), Ok(2));
+        assert_eq!(net.state_space_size(&token, 1), Err(Unbounded(1)));
 
-/// Weighted place-transition arcs.
-pub trait Arcs<Net: NetId> {
-    /// Returns a vector of erased arcs.
-    fn erased() -> Vec<(PlaceMetadata<Net>, usize)>;
-}
+        let net = choice();
+        let mut token = net.spawn_token();
+        net.mark::<P0>(&mut token, 1);
+        net.mark::<P1>(&mut token, 1);
+        net.mark::<P2>(&mut token, 1);
+        assert_eq!(net.state_space_size(&token, 10), Ok(3));
+    }
 
-// single place case
-impl<Net, P0, const W0: usize> Arcs<Net> for (P0, W<W0>)
-where
-    Net: NetId,
-    P0: Place<Net>,
-{
-    fn erased() -> Vec<(PlaceMetadata<Net>, usize)> {
-        vec![(PlaceMetadata::new::<P0>(), W0)]
+    #[test]
+    fn test_home_marking() {
+        let net = ring();
+        let mut home = net.spawn_token();
+        net.mark::<P0>(&mut home, 1);
+        let mut other = net.spawn_token();
+        net.mark::<P1>(&mut other, 1);
+        assert_eq!(net.is_home_marking(&home, &home, 10), Ok(true));
+        assert_eq!(net.is_home_marking(&home, &other, 10), Ok(true));
+
+        // Without |t1|, the token cannot return to (p0)
+        let net = PetriNet::<Ring>::new()
+            .add_place::<P0>()
+            .add_place::<P1>()
+            .add_trans::<T0, (P0, W<1>), (P1, W<1>)>();
+        assert_eq!(net.is_home_marking(&home, &home, 10), Ok(false));
+        assert_eq!(net.is_home_marking(&other, &home, 10), Ok(true));
     }
-}
 
-macro_rules! impl_arcs {
-    ($(($place:ident, $weight:ident)),*) => {
-        #[allow(unused_parens)]
-        impl<Net, $($place, const $weight: usize),*> Arcs<Net> for ($(($place, W<$weight>),)*)
-        where
-            Net: NetId,
-            $($place: Place<Net>),*
-        {
-            fn erased() -> Vec<(PlaceMetadata<Net>, usize)> {
-                vec![$((PlaceMetadata::new::<$place>(), $weight)),*]
-            }
-        }
-    };
-}
+    #[test]
+    fn test_reversibility() {
+        let net = ring();
+        let mut token = net.spawn_token();
+        net.mark::<P0>(&mut token, 2);
+        assert_eq!(net.is_reversible(&token, 10), Ok(true));
 
-all_tuples!(impl_arcs, 0, 15, P, W);
+        // Once |t0| fires, the token cannot return to (p0)
+        let net = minimal();
+        let mut token = net.spawn_token();
+        net.mark::<P0>(&mut token, 1);
+        assert_eq!(net.is_reversible(&token, 10), Ok(false));
 
-#[cfg(test)]
-mod tests {
-    use crate::{NetId, PetriNet, Place, Pn, Tn, Trans, W};
+        // The producer marks (p0) without bound, so not every reachable marking can be checked
+        let net = producer_consumer();
+        let token = net.spawn_token();
+        assert_eq!(net.is_reversible(&token, 10), Err(Unbounded(10)));
+    }
 
-    enum Minimal {}
-    enum ProdCons {}
-    enum Star {}
-    enum Ring {}
-    enum Choice {}
-    enum Anon<const MIXED: bool> {}
+    #[test]
+    fn test_state_space_size_of_unbounded_net() {
+        let net = producer_consumer();
+        let token = net.spawn_token();
+        assert_eq!(net.state_space_size(&token, 100), Err(Unbounded(100)));
+    }
 
-    enum P0 {}
-    enum P1 {}
-    enum P2 {}
-    enum P3 {}
-    enum P4 {}
+    #[test]
+    fn test_can_cover() {
+        let net = producer_consumer();
+        let (p0, _) = net.place::<P0>();
+        let token = net.spawn_token();
+        for k in [0, 1, 10, 50] {
+            assert_eq!(net.can_cover(&token, p0, k, 100), Ok(true));
+        }
+        assert_eq!(net.can_cover(&token, p0, 50, 10), Err(Unbounded(10)));
 
-    enum T0 {}
-    enum T1 {}
+        let net = ring();
+        let (p0, _) = net.place::<P0>();
+        let (p1, _) = net.place::<P1>();
+        let mut token = net.spawn_token();
+        net.mark::<P0>(&mut token, 1);
+        assert_eq!(net.can_cover(&token, p0, 1, 10), Ok(true));
+        assert_eq!(net.can_cover(&token, p1, 1, 2), Ok(true));
+        assert_eq!(net.can_cover(&token, p1, 1, 1), Err(Unbounded(1)));
+        assert_eq!(net.can_cover(&token, p0, 2, 10), Ok(false));
+    }
 
-    impl NetId for Minimal {}
-    impl NetId for ProdCons {}
-    impl NetId for Star {}
-    impl NetId for Ring {}
-    impl NetId for Choice {}
-    impl<const MIXED: bool> NetId for Anon<MIXED> {}
+    #[test]
+    fn test_reachability_is_bounded() {
+        let net = producer_consumer();
+        let from = net.spawn_token();
+        let mut target = net.spawn_token();
+        net.mark::<P0>(&mut target, 5);
+        assert!(net.is_reachable(&from, &target, 6));
+        assert!(!net.is_reachable(&from, &target, 5));
+    }
 
-    impl<Net: NetId> Place<Net> for P0 {}
-    impl<Net: NetId> Place<Net> for P1 {}
-    impl<Net: NetId> Place<Net> for P2 {}
-    impl<Net: NetId> Place<Net> for P3 {}
-    impl<Net: NetId> Place<Net> for P4 {}
+    #[test]
+    fn test_reachability_at_the_exact_limit() {
+        let net = choice();
+        let mut from = net.spawn_token();
+        net.mark::<P0>(&mut from, 1);
+        net.mark::<P1>(&mut from, 1);
+        net.mark::<P2>(&mut from, 1);
+        let mut first = from.clone();
+        assert!(net.fire::<T0>(&mut first).is_ok());
+        let mut second = from.clone();
+        assert!(net.fire::<T1>(&mut second).is_ok());
+        assert!(net.is_reachable(&from, &from, 1));
+        assert!(net.is_reachable(&from, &first, 2));
+        assert!(!net.is_reachable(&from, &second, 2));
+        assert!(net.is_reachable(&from, &second, 3));
+    }
 
-    impl<Net: NetId> Trans<Net> for T0 {}
-    impl<Net: NetId> Trans<Net> for T1 {}
+    #[test]
+    fn test_choice() {
+        let net = choice();
+        let mut token = net.spawn_token();
+        net.mark::<P0>(&mut token, 1);
+        net.mark::<P1>(&mut token, 1);
+        net.mark::<P2>(&mut token, 1);
+        assert!(net.enabled::<T0>(&token));
+        assert!(net.enabled::<T1>(&token));
+        assert!(net.fire::<T0>(&mut token).is_ok());
+        assert!(!net.enabled::<T1>(&token));
+    }
 
-    // (p0) -> |t0| -> (p1)
-    fn minimal() -> PetriNet<Minimal> {
+    // Same as `minimal`, but with `u64` marks.
+    // (p0) -2-> |t0| -> (p1)
+    fn wide() -> PetriNet<Wide, u64> {
         PetriNet::new()
             .add_place::<P0>()
             .add_place::<P1>()
-            .add_trans::<T0, (P0, W<1>), (P1, W<1>)>()
+            .add_trans::<T0, (P0, W<2>), (P1, W<1>)>()
     }
 
-    // Transitions with no input places are token sources,
-    // and transitions with no output places are token sinks
-    // |t0| -> (p0) -> |t1|
-    fn producer_consumer() -> PetriNet<ProdCons> {
-        PetriNet::new()
-            .add_place::<P0>()
-            .add_trans::<T0, (), (P0, W<1>)>()
-            .add_trans::<T1, (P0, W<1>), ()>()
+    #[test]
+    fn test_wide() {
+        let net = wide();
+        let mut token = net.spawn_token();
+        net.mark::<P0>(&mut token, u64::from(u32::MAX) + 2);
+        assert!(net.fire::<T0>(&mut token).is_ok());
+        assert_eq!(net.marks::<P0>(&token), u64::from(u32::MAX));
+        assert_eq!(net.marks::<P1>(&token), 1);
     }
 
-    // (p0) -\            /-> (p2)
-    //        >-> |t0| --<--> (p3)
-    // (p1) -/            \-> (p4)
-    fn weighted_star() -> PetriNet<Star> {
-        PetriNet::new()
-            .add_place::<P0>()
-            .add_place::<P1>()
-            .add_place::<P2>()
-            .add_place::<P3>()
-            .add_place::<P4>()
-            .add_trans::<T0, ((P0, W<1>), (P1, W<2>)), ((P2, W<1>), (P3, W<2>), (P4, W<3>))>()
+    #[test]
+    fn test_validate() {
+        assert!(minimal().validate().is_ok());
+        let net = minimal().add_place::<P2>();
+        let (p2, _) = net.place::<P2>();
+        assert_eq!(net.validate(), Err(vec![NetWarning::IsolatedPlace(p2)]));
     }
 
-    // Two places sending a token back and forth through two transitions in opposite directions:
-    //  /--> |t0| -> (p1)
-    // (p0) <- |t1| <--/
-    fn ring() -> PetriNet<Ring> {
-        PetriNet::new()
+    #[test]
+    #[should_panic(expected = "weight of zero")]
+    fn test_zero_weight_is_rejected() {
+        let _ = PetriNet::<Minimal>::new()
             .add_place::<P0>()
             .add_place::<P1>()
-            .add_trans::<T0, (P0, W<1>), (P1, W<1>)>()
-            .add_trans::<T1, (P1, W<1>), (P0, W<1>)>()
+            .add_trans::<T0, (P0, W<1>), (P1, W<0>)>();
     }
 
-    // Two transitions sharing a preset place. When one of them fires, the other ceases to be enabled.
-    // (p0) --> |t0| -\
-    // (p1) -<         >-> (p3)
-    // (p2) --> |t1| -/
-    fn choice() -> PetriNet<Choice> {
-        PetriNet::new()
+    #[test]
+    fn test_validate_reports_all_warnings() {
+        let mut net = PetriNet::<Anon<false>>::new();
+        let p0 = net.add_place_anon("p0");
+        let p1 = net.add_place_anon("p1");
+        let t0 = net.add_trans_anon("t0", &[(p0, 0)], &[]);
+        let t1 = net.add_trans_anon("t1", &[], &[]);
+        let warnings = net.validate().unwrap_err();
+        assert_eq!(warnings.len(), 3);
+        assert!(warnings.contains(&NetWarning::ZeroWeight(t0, p0)));
+        assert!(warnings.contains(&NetWarning::IsolatedTrans(t1)));
+        assert!(warnings.contains(&NetWarning::IsolatedPlace(p1)));
+    }
+
+    #[test]
+    fn test_sorted_by_name() {
+        let mut net = PetriNet::<Anon<false>>::new();
+        let p = ["pb", "pc", "pa"].map(|pn| net.add_place_anon(pn));
+        let t1 = net.add_trans_anon("tb", &[(p[0], 1)], &[(p[1], 1)]);
+        let t0 = net.add_trans_anon("ta", &[(p[1], 1)], &[(p[2], 1)]);
+        let places: Vec<_> = net.places_sorted().into_iter().map(|(id, _)| id).collect();
+        assert_eq!(places, [p[2], p[0], p[1]]);
+        let transitions: Vec<_> = net
+            .transitions_sorted()
+            .into_iter()
+            .map(|(id, meta)| (id, meta.name()))
+            .collect();
+        assert_eq!(transitions, [(t0, "ta"), (t1, "tb")]);
+    }
+
+    #[test]
+    fn test_guard_blocks_enabled_transition() {
+        let net = PetriNet::<Minimal>::new()
             .add_place::<P0>()
             .add_place::<P1>()
-            .add_place::<P2>()
-            .add_place::<P3>()
-            .add_trans::<T0, ((P0, W<1>), (P1, W<1>)), (P3, W<1>)>()
-            .add_trans::<T1, ((P1, W<1>), (P2, W<1>)), (P3, W<1>)>()
+            .add_trans_guarded::<T0, (P0, W<1>), (P1, W<1>)>(|_| false);
+        let mut token = net.spawn_token();
+        net.mark::<P0>(&mut token, 1);
+        assert!(!net.enabled::<T0>(&token));
+        assert!(net.fire::<T0>(&mut token).is_err());
+        assert_eq!(net.marks::<P0>(&token), 1);
     }
 
     #[test]
-    fn test_minimal() {
-        let net = minimal();
+    fn test_guard_reads_token() {
+        // T0 only fires while there are at least 2 marks in total
+        let net = PetriNet::<Minimal>::new()
+            .add_place::<P0>()
+            .add_place::<P1>()
+            .add_trans_guarded::<T0, (P0, W<1>), (P1, W<1>)>(|token| token.total_marks() >= 2);
         let mut token = net.spawn_token();
         net.mark::<P0>(&mut token, 1);
+        assert!(!net.enabled::<T0>(&token));
+        net.mark::<P1>(&mut token, 1);
         assert!(net.fire::<T0>(&mut token).is_ok());
-        assert_eq!(net.marks::<P0>(&token), 0);
-        assert_eq!(net.marks::<P1>(&token), 1);
     }
 
     #[test]
-    fn test_producer_consumer() {
-        let net = producer_consumer();
+    fn test_places_in_group() {
+        let net = PetriNet::<Choice>::new()
+            .add_place_in_group::<P0, _>("left")
+            .add_place_in_group::<P1, _>("right")
+            .add_place_in_group::<P2, _>("left")
+            .add_place::<P3>();
+        let [p0, p1, p2, p3] = [
+            net.place::<P0>().0,
+            net.place::<P1>().0,
+            net.place::<P2>().0,
+            net.place::<P3>().0,
+        ];
+        assert_eq!(net.places_in_group("left").collect::<Vec<_>>(), [p0, p2]);
+        assert_eq!(net.places_in_group("right").collect::<Vec<_>>(), [p1]);
+        assert_eq!(net.places_in_group("middle").count(), 0);
+        assert_eq!(net.group_of(p0), Some("left"));
+        assert_eq!(net.group_of(p3), None);
+    }
+
+    #[test]
+    fn test_consumers_and_producers() {
+        enum Philosophers {}
+        enum ForkFree {}
+        enum ForkTaken {}
+        enum Take {}
+        enum Eat {}
+        impl NetId for Philosophers {}
+        impl Place<Philosophers> for ForkFree {}
+        impl Place<Philosophers> for ForkTaken {}
+        impl Trans<Philosophers> for Take {}
+        impl Trans<Philosophers> for Eat {}
+
+        // (fork_free) -> |take| -> (fork_taken) -> |eat| -> (fork_free)
+        let net = PetriNet::<Philosophers>::new()
+            .add_place::<ForkFree>()
+            .add_place::<ForkTaken>()
+            .add_trans::<Take, (ForkFree, W<2>), (ForkTaken, W<2>)>()
+            .add_trans::<Eat, (ForkTaken, W<2>), (ForkFree, W<2>)>();
+        let fork_taken = net.place::<ForkTaken>().0;
+        let take = net.trans::<Take>().0;
+        let eat = net.trans::<Eat>().0;
+        assert_eq!(net.consumers(fork_taken).collect::<Vec<_>>(), [eat]);
+        assert_eq!(net.producers(fork_taken).collect::<Vec<_>>(), [take]);
+    }
+
+    #[test]
+    fn test_add_inflow_after_registration() {
+        let mut net = producer_consumer();
+        let (p0, _) = net.place::<P0>();
+        let (t0, _) = net.trans::<T0>();
         let mut token = net.spawn_token();
+        assert!(net.enabled::<T0>(&token));
+        // |t0| now consumes from the place it produces into
+        net.add_inflow(t0, p0, 1);
+        assert!(!net.enabled::<T0>(&token));
+        net.mark::<P0>(&mut token, 1);
         assert!(net.fire::<T0>(&mut token).is_ok());
         assert_eq!(net.marks::<P0>(&token), 1);
-        assert!(net.fire::<T1>(&mut token).is_ok());
-        assert_eq!(net.marks::<P0>(&token), 0);
     }
 
     #[test]
-    fn test_weighted_star() {
-        let net = weighted_star();
+    fn test_duplicate_inflow_adds_up() {
+        let mut net = minimal();
+        let (p0, _) = net.place::<P0>();
+        let (t0, _) = net.trans::<T0>();
+        net.add_inflow(t0, p0, 1);
+        assert_eq!(net.arc_weight(t0, p0), Some((Direction::Input, 2)));
         let mut token = net.spawn_token();
         net.mark::<P0>(&mut token, 1);
-        net.mark::<P1>(&mut token, 2);
+        assert!(!net.enabled::<T0>(&token));
+        assert_eq!(net.fire::<T0>(&mut token), Err(FireError::NotEnabled(t0)));
+        net.mark::<P0>(&mut token, 1);
         assert!(net.fire::<T0>(&mut token).is_ok());
         assert_eq!(net.marks::<P0>(&token), 0);
-        assert_eq!(net.marks::<P1>(&token), 0);
-        assert_eq!(net.marks::<P2>(&token), 1);
-        assert_eq!(net.marks::<P3>(&token), 2);
-        assert_eq!(net.marks::<P4>(&token), 3);
+
+        let net = PetriNet::<Minimal>::new()
+            .add_place::<P0>()
+            .add_place::<P1>()
+            .add_trans::<T0, ((P0, W<1>), (P0, W<1>)), (P1, W<1>)>();
+        let mut token = net.spawn_token();
+        net.mark::<P0>(&mut token, 1);
+        assert!(net.fire::<T0>(&mut token).is_err());
     }
 
     #[test]
-    fn test_ring() {
-        let net = ring();
+    fn test_cloned_net_is_independent() {
+        let net = minimal();
+        let mut fork = net.clone();
+        let (p1, _) = fork.place::<P1>();
+        let (t0, _) = fork.trans::<T0>();
+        fork.add_inflow(t0, p1, 1);
+        let fork = fork.add_place::<P2>();
+        assert_eq!(net.places().count(), 2);
+        assert_eq!(fork.places().count(), 3);
+
+        let mut token = net.spawn_token();
+        net.mark::<P0>(&mut token, 1);
+        assert!(net.enabled::<T0>(&token));
+        assert!(!fork.enabled::<T0>(&token));
+    }
+
+    #[test]
+    fn test_add_outflow_after_registration() {
+        let mut net = minimal();
+        let (p0, _) = net.place::<P0>();
+        let (t0, _) = net.trans::<T0>();
+        net.add_outflow(t0, p0, 1);
         let mut token = net.spawn_token();
         net.mark::<P0>(&mut token, 1);
-        assert_eq!(net.marks::<P0>(&token), 1);
-        assert_eq!(net.marks::<P1>(&token), 0);
         assert!(net.fire::<T0>(&mut token).is_ok());
-        assert_eq!(net.marks::<P0>(&token), 0);
-        assert_eq!(net.marks::<P1>(&token), 1);
-        assert!(net.fire::<T1>(&mut token).is_ok());
         assert_eq!(net.marks::<P0>(&token), 1);
-        assert_eq!(net.marks::<P1>(&token), 0);
+        assert_eq!(net.marks::<P1>(&token), 1);
     }
 
     #[test]
-    fn test_choice() {
-        let net = choice();
+    #[should_panic(expected = "not found in net")]
+    fn test_add_inflow_out_of_range() {
+        let mut net = minimal();
+        let (t0, _) = net.trans::<T0>();
+        net.add_inflow(t0, PlaceId::new(2), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "not found in net")]
+    fn test_add_trans_dyn_out_of_range() {
+        let mut net = PetriNet::<Minimal>::new().add_place::<P0>();
+        let (p0, _) = net.place::<P0>();
+        let _ = net.add_trans_dyn::<T0>(&[(p0, 1)], &[(PlaceId::new(99), 1)]);
+    }
+
+    #[test]
+    fn test_self_loop() {
+        // (p0) <-> |t0| -> (p1)
+        //   ^-----> |t1|
+        let net = PetriNet::<Minimal>::new()
+            .add_place::<P0>()
+            .add_place::<P1>()
+            .add_trans::<T0, (P0, W<1>), ((P0, W<1>), (P1, W<1>))>()
+            .add_trans::<T1, (P0, W<2>), (P0, W<2>)>();
+        assert!(!net.is_self_loop::<T0>());
+        assert!(net.is_self_loop::<T1>());
+
+        // The self-loop consumes before it produces, so it needs the marks to be present
         let mut token = net.spawn_token();
         net.mark::<P0>(&mut token, 1);
-        net.mark::<P1>(&mut token, 1);
-        net.mark::<P2>(&mut token, 1);
-        assert!(net.enabled::<T0>(&token));
-        assert!(net.enabled::<T1>(&token));
-        assert!(net.fire::<T0>(&mut token).is_ok());
         assert!(!net.enabled::<T1>(&token));
+        assert!(net.fire::<T1>(&mut token).is_err());
+        assert_eq!(net.marks::<P0>(&token), 1);
+
+        // Once enabled, firing leaves the marking unchanged
+        net.mark::<P0>(&mut token, 1);
+        let before = token.clone();
+        assert!(net.fire::<T1>(&mut token).is_ok());
+        assert_eq!(token, before);
+    }
+
+    #[test]
+    fn test_dyn_trans_fires_like_typed_trans() {
+        let typed = weighted_star();
+        let mut dynamic = PetriNet::<Star>::new()
+            .add_place::<P0>()
+            .add_place::<P1>()
+            .add_place::<P2>()
+            .add_place::<P3>()
+            .add_place::<P4>();
+        let [p0, p1, p2, p3, p4] = [
+            dynamic.place::<P0>().0,
+            dynamic.place::<P1>().0,
+            dynamic.place::<P2>().0,
+            dynamic.place::<P3>().0,
+            dynamic.place::<P4>().0,
+        ];
+        let cost = 2;
+        let t0 = dynamic.add_trans_dyn::<T0>(&[(p0, 1), (p1, cost)], &[(p2, 1), (p3, 2), (p4, 3)]);
+        assert_eq!(dynamic.trans::<T0>().0, t0);
+        let mut typed_token = typed.spawn_token();
+        let mut dynamic_token = dynamic.spawn_token();
+        for (net, token) in [(&typed, &mut typed_token), (&dynamic, &mut dynamic_token)] {
+            net.mark::<P0>(token, 1);
+            net.mark::<P1>(token, 1);
+            assert!(net.fire::<T0>(token).is_err());
+            net.mark::<P1>(token, 1);
+            assert!(net.fire::<T0>(token).is_ok());
+        }
+        assert_eq!(typed_token, dynamic_token);
     }
 
     #[test]
@@ -470,6 +3049,23 @@ mod tests {
         assert_eq!(net.marks_by_id(p[2], &token), 1);
     }
 
+    #[test]
+    fn test_structure_hash_ignores_registration_order() {
+        let mut a = PetriNet::<Anon<false>>::new();
+        let [p0, p1] = ["p0", "p1"].map(|pn| a.add_place_anon(pn));
+        let _ = a.add_trans_anon("t0", &[(p0, 1)], &[(p1, 2)]);
+        let _ = a.add_trans_anon("t1", &[(p1, 2)], &[(p0, 1)]);
+
+        let mut b = PetriNet::<Anon<false>>::new();
+        let [p1, p0] = ["p1", "p0"].map(|pn| b.add_place_anon(pn));
+        let _ = b.add_trans_anon("t1", &[(p1, 2)], &[(p0, 1)]);
+        let _ = b.add_trans_anon("t0", &[(p0, 1)], &[(p1, 2)]);
+        assert_eq!(a.structure_hash(), b.structure_hash());
+
+        let _ = b.add_trans_anon("t2", &[(p0, 1)], &[]);
+        assert_ne!(a.structure_hash(), b.structure_hash());
+    }
+
     #[test]
     fn test_mixed_net() {
         let mut net = PetriNet::<Anon<true>>::new();
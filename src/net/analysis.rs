@@ -0,0 +1,334 @@
+//! Petri net analysis.
+
+use std::collections::VecDeque;
+
+use bevy_utils::{HashMap, HashSet};
+
+use super::place::PlaceId;
+use super::token::Token;
+use super::trans::{Inflow, Outflow, TransId};
+use super::{Marks, NetId, PetriNet, Unbounded};
+
+/// Largest number of places whose sets are enumerated, as `n` places have `2^n` sets.
+const MAX_ENUMERATED_PLACES: usize = 20;
+
+/// Returns the non-empty subsets of `num_places` places as bit masks, smallest first.
+fn sets_by_size(num_places: usize) -> impl Iterator<Item = u64> {
+    (1..=num_places).flat_map(move |size| {
+        // Gosper's hack: the next larger mask with the same number of bits set.
+        let next = |set: &u64| {
+            let lowest = set & set.wrapping_neg();
+            let ripple = set + lowest;
+            Some((((ripple ^ set) >> 2) / lowest) | ripple)
+        };
+        std::iter::successors(Some((1 << size) - 1), next)
+            .take_while(move |&set| set < 1 << num_places)
+    })
+}
+
+/// Outcome of exploring the markings reachable from an initial marking.
+enum Exploration<T> {
+    /// The visitor stopped the exploration with a value.
+    Stopped(T),
+    /// Every reachable marking was visited. Holds their number.
+    Complete(usize),
+    /// More markings are reachable than the exploration was allowed to visit.
+    Truncated,
+}
+
+impl<Net: NetId, M: Marks> PetriNet<Net, M> {
+    /// Returns whether the `target` marking can be reached from the `from` marking
+    /// by firing a sequence of transitions.
+    ///
+    /// Explores at most `max_states` distinct markings breadth-first,
+    /// and returns `false` if the `target` has not been found by then.
+    #[must_use]
+    pub fn is_reachable(
+        &self,
+        from: &Token<Net, M>,
+        target: &Token<Net, M>,
+        max_states: usize,
+    ) -> bool {
+        self.fire_sequence_to(from, target, max_states).is_some()
+    }
+
+    /// Returns a shortest sequence of transitions that transforms the `from` marking
+    /// into the `target` marking.
+    ///
+    /// Explores at most `max_states` distinct markings breadth-first,
+    /// and returns `None` if the `target` has not been found by then.
+    #[must_use]
+    pub fn fire_sequence_to(
+        &self,
+        from: &Token<Net, M>,
+        target: &Token<Net, M>,
+        max_states: usize,
+    ) -> Option<Vec<TransId<Net>>> {
+        if max_states == 0 {
+            return None;
+        }
+        if from == target {
+            return Some(Vec::new());
+        }
+        // Each discovered marking maps to its predecessor and the transition leading from it.
+        let mut predecessors = HashMap::new();
+        let exploration = self.explore(from, max_states, |prev, trans, next, is_new| {
+            if !is_new {
+                return None;
+            }
+            predecessors.insert(next.clone(), (prev.clone(), trans));
+            (next == target).then_some(())
+        });
+        if !matches!(exploration, Exploration::Stopped(())) {
+            return None;
+        }
+        let mut sequence = Vec::new();
+        let mut current = target;
+        while let Some((prev, trans)) = predecessors.get(current) {
+            sequence.push(*trans);
+            current = prev;
+        }
+        sequence.reverse();
+        Some(sequence)
+    }
+
+    /// Returns whether a marking with at least `k` marks in `place` can be reached
+    /// from the `from` marking by firing a sequence of transitions.
+    ///
+    /// Explores at most `max_states` distinct markings breadth-first.
+    /// Since any marking covering `k` marks will do, this can answer for unbounded places.
+    ///
+    /// ## Errors
+    ///
+    /// Returns [`Unbounded`] if no such marking has been found among the first `max_states`
+    /// markings, and more markings are reachable.
+    pub fn can_cover(
+        &self,
+        from: &Token<Net, M>,
+        place: PlaceId<Net>,
+        k: M,
+        max_states: usize,
+    ) -> Result<bool, Unbounded> {
+        if max_states > 0 && from.marks_by_id(place) >= k {
+            return Ok(true);
+        }
+        let exploration = self.explore(from, max_states, |_, _, next, is_new| {
+            (is_new && next.marks_by_id(place) >= k).then_some(())
+        });
+        match exploration {
+            Exploration::Stopped(()) => Ok(true),
+            Exploration::Complete(_) => Ok(false),
+            Exploration::Truncated => Err(Unbounded(max_states)),
+        }
+    }
+
+    /// Returns the number of distinct markings reachable from the `initial` marking,
+    /// including the `initial` marking itself.
+    ///
+    /// ## Errors
+    ///
+    /// Returns [`Unbounded`] if more than `max_states` markings are reachable.
+    pub fn state_space_size(
+        &self,
+        initial: &Token<Net, M>,
+        max_states: usize,
+    ) -> Result<usize, Unbounded> {
+        match self.explore(initial, max_states, |_, _, _, _| None::<()>) {
+            Exploration::Complete(size) => Ok(size),
+            Exploration::Stopped(()) | Exploration::Truncated => Err(Unbounded(max_states)),
+        }
+    }
+
+    /// Returns whether the `target` marking can be reached from every marking
+    /// reachable from the `initial` marking.
+    ///
+    /// Explores at most `max_states` distinct markings breadth-first.
+    ///
+    /// ## Errors
+    ///
+    /// Returns [`Unbounded`] if more than `max_states` markings are reachable.
+    pub fn is_home_marking(
+        &self,
+        target: &Token<Net, M>,
+        initial: &Token<Net, M>,
+        max_states: usize,
+    ) -> Result<bool, Unbounded> {
+        // Each visited marking maps to the markings it can be reached from in one step.
+        let mut predecessors = HashMap::from_iter([(initial.clone(), Vec::new())]);
+        let exploration = self.explore(initial, max_states, |prev, _, next, _| {
+            predecessors
+                .entry(next.clone())
+                .or_insert_with(Vec::new)
+                .push(prev.clone());
+            None::<()>
+        });
+        if !matches!(exploration, Exploration::Complete(_)) {
+            return Err(Unbounded(max_states));
+        }
+        if !predecessors.contains_key(target) {
+            return Ok(false);
+        }
+        // Walk the reachability graph backwards from the target.
+        let mut reaching = HashSet::from_iter([target]);
+        let mut stack = vec![target];
+        while let Some(token) = stack.pop() {
+            for prev in &predecessors[token] {
+                if reaching.insert(prev) {
+                    stack.push(prev);
+                }
+            }
+        }
+        Ok(reaching.len() == predecessors.len())
+    }
+
+    /// Returns whether the `initial` marking can be reached again from every marking
+    /// reachable from it.
+    ///
+    /// Explores at most `max_states` distinct markings breadth-first.
+    ///
+    /// ## Errors
+    ///
+    /// Returns [`Unbounded`] if more than `max_states` markings are reachable.
+    pub fn is_reversible(
+        &self,
+        initial: &Token<Net, M>,
+        max_states: usize,
+    ) -> Result<bool, Unbounded> {
+        self.is_home_marking(initial, initial, max_states)
+    }
+
+    /// Returns the minimal siphons of the net.
+    ///
+    /// A siphon is a set of places such that every transition that marks a place in the set
+    /// also consumes from a place in the set. Once a siphon is unmarked, it stays unmarked.
+    ///
+    /// The siphons are found by trying every set of places, so this is only suited to small nets.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the net has more than 20 places.
+    #[must_use]
+    pub fn siphons(&self) -> Vec<Vec<PlaceId<Net>>> {
+        self.minimal_place_sets(|inputs, outputs, set| outputs & set == 0 || inputs & set != 0)
+    }
+
+    /// Returns the minimal traps of the net.
+    ///
+    /// A trap is a set of places such that every transition that consumes from a place in the set
+    /// also marks a place in the set. Once a trap is marked, it stays marked.
+    ///
+    /// The traps are found by trying every set of places, so this is only suited to small nets.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the net has more than 20 places.
+    #[must_use]
+    pub fn traps(&self) -> Vec<Vec<PlaceId<Net>>> {
+        self.minimal_place_sets(|inputs, outputs, set| inputs & set == 0 || outputs & set != 0)
+    }
+
+    /// Returns the minimal non-empty sets of places for which `holds` is true for every transition,
+    /// given the input places, the output places, and the set as bit masks.
+    fn minimal_place_sets(&self, holds: impl Fn(u64, u64, u64) -> bool) -> Vec<Vec<PlaceId<Net>>> {
+        let num_places = self.places.len();
+        assert!(
+            num_places <= MAX_ENUMERATED_PLACES,
+            "Net `{}` has too many places to enumerate their sets.",
+            std::any::type_name::<Net>()
+        );
+        let masks: Vec<_> = self
+            .transitions
+            .ids()
+            .map(|trans| {
+                let inputs = self
+                    .flows
+                    .inflows(trans)
+                    .iter()
+                    .fold(0, |mask, &Inflow { source, .. }| mask | 1 << source.index());
+                let outputs = self
+                    .flows
+                    .outflows(trans)
+                    .iter()
+                    .fold(0, |mask, &Outflow { target, .. }| {
+                        mask | 1 << target.index()
+                    });
+                (inputs, outputs)
+            })
+            .collect();
+        let mut minimal: Vec<u64> = Vec::new();
+        for set in sets_by_size(num_places) {
+            if minimal.iter().any(|&found| found & !set == 0) {
+                continue;
+            }
+            if masks
+                .iter()
+                .all(|&(inputs, outputs)| holds(inputs, outputs, set))
+            {
+                minimal.push(set);
+            }
+        }
+        minimal
+            .into_iter()
+            .map(|set| {
+                (0..num_places)
+                    .filter(|index| set & 1 << index != 0)
+                    .map(PlaceId::new)
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Explores the markings reachable from the `initial` marking breadth-first,
+    /// visiting at most `max_states` distinct markings, including the `initial` one.
+    ///
+    /// `visit` is called for every step from a visited marking to a visited marking,
+    /// with the transition fired and whether the reached marking was just discovered.
+    /// Markings beyond the limit are skipped, but the queued markings are still expanded.
+    /// Returning `Some` from `visit` stops the exploration.
+    fn explore<T>(
+        &self,
+        initial: &Token<Net, M>,
+        max_states: usize,
+        mut visit: impl FnMut(&Token<Net, M>, TransId<Net>, &Token<Net, M>, bool) -> Option<T>,
+    ) -> Exploration<T> {
+        if max_states == 0 {
+            return Exploration::Truncated;
+        }
+        let mut visited = HashSet::from_iter([initial.clone()]);
+        let mut queue = VecDeque::from([initial.clone()]);
+        let mut truncated = false;
+        while let Some(token) = queue.pop_front() {
+            for (trans, next) in self.successors(&token) {
+                let is_new = !visited.contains(&next);
+                if is_new && visited.len() == max_states {
+                    truncated = true;
+                    continue;
+                }
+                if let Some(value) = visit(&token, trans, &next, is_new) {
+                    return Exploration::Stopped(value);
+                }
+                if is_new {
+                    visited.insert(next.clone());
+                    queue.push_back(next);
+                }
+            }
+        }
+        if truncated {
+            Exploration::Truncated
+        } else {
+            Exploration::Complete(visited.len())
+        }
+    }
+
+    /// Returns the markings obtained by firing each transition enabled by the token.
+    fn successors<'a>(
+        &'a self,
+        token: &'a Token<Net, M>,
+    ) -> impl Iterator<Item = (TransId<Net>, Token<Net, M>)> + 'a {
+        self.transitions.ids().filter_map(|trans| {
+            let mut next = token.clone();
+            self.fire_silently(trans, &mut next).ok()?;
+            Some((trans, next))
+        })
+    }
+}
@@ -0,0 +1,289 @@
+//! Petri net with precomputed firing effects.
+
+use std::ops::{Deref, Range};
+
+use fixedbitset::FixedBitSet;
+
+use super::place::PlaceId;
+use super::token::Token;
+use super::trans::{Inflow, Outflow, Trans, TransHandle, TransId};
+use super::{FireError, Guard, Marks, NetId, NotEnabled, PetriNet};
+
+/// Petri net with the effect of firing each transition precomputed, for faster firing in hot loops.
+///
+/// Created with [`PetriNet::compile`]. Fires transitions exactly like the net it was compiled from,
+/// but visits each place connected to a transition once, without looking up capacities or guards.
+///
+/// Only the methods of [`CompiledNet`] use the precomputed effects. The methods reached
+/// through [`Deref`], such as [`PetriNet::step_fair`], [`PetriNet::simulate`]
+/// and the analyses, fire the transitions of the net it was compiled from.
+pub struct CompiledNet<Net: NetId, M: Marks = usize> {
+    net: PetriNet<Net, M>,
+    /// Effects of every transition, grouped by transition.
+    effects: Vec<Effect<Net, M>>,
+    transitions: Vec<CompiledTrans<Net, M>>,
+}
+
+/// Precomputed firing of a transition.
+struct CompiledTrans<Net: NetId, M: Marks> {
+    /// Range of the effects of the transition.
+    effects: Range<usize>,
+    guard: Option<Guard<Net, M>>,
+}
+
+/// Effect of firing a transition on one of the places connected to it.
+struct Effect<Net: NetId, M: Marks> {
+    place: PlaceId<Net>,
+    /// Weight of the inflow from the place, if any.
+    consumed: M,
+    /// Weight of the outflow to the place, if any.
+    produced: M,
+    /// Capacity of the place, if it is an output place.
+    capacity: Option<M>,
+}
+
+impl<Net: NetId, M: Marks> PetriNet<Net, M> {
+    /// Precomputes the effect of firing each transition of the net.
+    ///
+    /// The net cannot be modified once compiled. Use [`CompiledNet::into_inner`]
+    /// to get it back.
+    #[must_use]
+    pub fn compile(self) -> CompiledNet<Net, M> {
+        let mut effects = Vec::new();
+        let transitions = self
+            .transitions
+            .ids()
+            .map(|trans| {
+                let start = effects.len();
+                // Arcs between the same pair are merged, so each place has at most
+                // one inflow and one outflow. Output places come first, in the order
+                // of the outflows, so that errors are reported like the net does.
+                let inflows = self.flows.inflows(trans);
+                let outflows = self.flows.outflows(trans);
+                for &Outflow { target, weight } in outflows {
+                    effects.push(Effect {
+                        place: target,
+                        consumed: inflows
+                            .iter()
+                            .find(|inflow| inflow.source == target)
+                            .map_or_else(M::default, |inflow| inflow.weight),
+                        produced: weight,
+                        capacity: self.capacity_by_id(target),
+                    });
+                }
+                for &Inflow { source, weight } in inflows {
+                    if outflows.iter().all(|outflow| outflow.target != source) {
+                        effects.push(Effect {
+                            place: source,
+                            consumed: weight,
+                            produced: M::default(),
+                            capacity: None,
+                        });
+                    }
+                }
+                CompiledTrans {
+                    effects: start..effects.len(),
+                    guard: self.guards.get(&trans).copied(),
+                }
+            })
+            .collect();
+        CompiledNet {
+            net: self,
+            effects,
+            transitions,
+        }
+    }
+}
+
+impl<Net: NetId, M: Marks> CompiledNet<Net, M> {
+    /// Returns the net this was compiled from.
+    #[must_use]
+    pub fn into_inner(self) -> PetriNet<Net, M> {
+        self.net
+    }
+
+    /// Returns whether a transition is enabled.
+    #[must_use]
+    pub fn enabled<T: Trans<Net>>(&self, token: &Token<Net, M>) -> bool {
+        let trans = self.net.transitions.id::<T>();
+        self.enabled_by_id(trans, token)
+    }
+
+    /// Fires a transition.
+    ///
+    /// ## Errors
+    ///
+    /// Returns a [`FireError`] if the transition is not enabled,
+    /// or if marking an output place would exceed its capacity or overflow `M`.
+    pub fn fire<T: Trans<Net>>(&self, token: &mut Token<Net, M>) -> Result<(), FireError<Net>> {
+        let trans = self.net.transitions.id::<T>();
+        self.fire_by_id(trans, token)
+    }
+
+    /// Returns whether a transition is enabled.
+    ///
+    /// See [`PetriNet::enabled_by_id`].
+    #[must_use]
+    pub fn enabled_by_id(&self, trans: TransId<Net>, token: &Token<Net, M>) -> bool {
+        let CompiledTrans { effects, guard } = &self.transitions[trans.index()];
+        self.effects[effects.clone()]
+            .iter()
+            .all(|effect| token.marks_by_id(effect.place) >= effect.consumed)
+            && guard.is_none_or(|guard| guard(token))
+    }
+
+    /// Fires transition.
+    ///
+    /// See [`PetriNet::fire_by_id`].
+    ///
+    /// ## Errors
+    ///
+    /// Returns a [`FireError`] if the transition is not enabled,
+    /// or if marking an output place would exceed its capacity or overflow `M`.
+    pub fn fire_by_id(
+        &self,
+        trans: TransId<Net>,
+        token: &mut Token<Net, M>,
+    ) -> Result<(), FireError<Net>> {
+        if !self.enabled_by_id(trans, token) {
+            return Err(NotEnabled(trans).into());
+        }
+        let effects = &self.effects[self.transitions[trans.index()].effects.clone()];
+        for effect in effects {
+            let marks = (token.marks_by_id(effect.place) - effect.consumed)
+                .checked_add(effect.produced)
+                .ok_or(FireError::Overflow(effect.place))?;
+            if effect.capacity.is_some_and(|capacity| marks > capacity) {
+                return Err(FireError::CapacityExceeded(effect.place));
+            }
+        }
+        for effect in effects {
+            if effect.consumed != effect.produced {
+                let marks = token.marks_by_id(effect.place) - effect.consumed + effect.produced;
+                token.set_marks_by_id(effect.place, marks);
+            }
+        }
+        for observer in &self.net.observers {
+            observer(trans, token);
+        }
+        Ok(())
+    }
+
+    /// Returns whether the transition of a handle is enabled.
+    #[must_use]
+    pub fn enabled_handle(&self, handle: TransHandle<Net>, token: &Token<Net, M>) -> bool {
+        self.enabled_by_id(handle.id(), token)
+    }
+
+    /// Fires the transition of a handle.
+    ///
+    /// ## Errors
+    ///
+    /// Returns a [`FireError`] if the transition is not enabled,
+    /// or if marking an output place would exceed its capacity or overflow `M`.
+    pub fn fire_handle(
+        &self,
+        handle: TransHandle<Net>,
+        token: &mut Token<Net, M>,
+    ) -> Result<(), FireError<Net>> {
+        self.fire_by_id(handle.id(), token)
+    }
+
+    /// Fires a transition, returning the marks it added to each output place.
+    ///
+    /// See [`PetriNet::fire_report_by_id`].
+    ///
+    /// ## Errors
+    ///
+    /// Returns a [`FireError`] if the transition could not be fired.
+    pub fn fire_report_by_id(
+        &self,
+        trans: TransId<Net>,
+        token: &mut Token<Net, M>,
+    ) -> Result<Vec<(PlaceId<Net>, M)>, FireError<Net>> {
+        self.fire_by_id(trans, token)?;
+        Ok(self
+            .net
+            .flows
+            .outflows(trans)
+            .iter()
+            .map(|&Outflow { target, weight }| (target, weight))
+            .collect())
+    }
+
+    /// Fires a transition, returning the marks it added to each output place.
+    ///
+    /// ## Errors
+    ///
+    /// Returns a [`FireError`] if the transition could not be fired.
+    pub fn fire_report<T: Trans<Net>>(
+        &self,
+        token: &mut Token<Net, M>,
+    ) -> Result<Vec<(PlaceId<Net>, M)>, FireError<Net>> {
+        let trans = self.net.transitions.id::<T>();
+        self.fire_report_by_id(trans, token)
+    }
+
+    /// Fires a transition up to `n` times, stopping early once it can no longer be fired.
+    ///
+    /// See [`PetriNet::fire_n_by_id`].
+    ///
+    /// ## Errors
+    ///
+    /// Returns a [`FireError`] if `n` is positive and the transition could not be fired even once.
+    pub fn fire_n_by_id(
+        &self,
+        trans: TransId<Net>,
+        token: &mut Token<Net, M>,
+        n: usize,
+    ) -> Result<usize, FireError<Net>> {
+        for fired in 0..n {
+            if let Err(err) = self.fire_by_id(trans, token) {
+                return if fired == 0 { Err(err) } else { Ok(fired) };
+            }
+        }
+        Ok(n)
+    }
+
+    /// Fires a transition up to `n` times, stopping early once it can no longer be fired.
+    ///
+    /// ## Errors
+    ///
+    /// Returns a [`FireError`] if `n` is positive and the transition could not be fired even once.
+    pub fn fire_n<T: Trans<Net>>(
+        &self,
+        token: &mut Token<Net, M>,
+        n: usize,
+    ) -> Result<usize, FireError<Net>> {
+        let trans = self.net.transitions.id::<T>();
+        self.fire_n_by_id(trans, token, n)
+    }
+
+    /// Fires the first transition that can be fired, in the order of registration.
+    ///
+    /// Returns the fired transition, or `None` if no transition could be fired.
+    pub fn step(&self, token: &mut Token<Net, M>) -> Option<TransId<Net>> {
+        self.net
+            .transitions
+            .ids()
+            .find(|&trans| self.fire_by_id(trans, token).is_ok())
+    }
+
+    /// Returns the enabled transitions as a bit set, indexed by [`TransId::index`].
+    #[must_use]
+    pub fn enabled_mask(&self, token: &Token<Net, M>) -> FixedBitSet {
+        let mut mask = FixedBitSet::with_capacity(self.transitions.len());
+        for trans in self.net.transitions.ids() {
+            mask.set(trans.index(), self.enabled_by_id(trans, token));
+        }
+        mask
+    }
+}
+
+impl<Net: NetId, M: Marks> Deref for CompiledNet<Net, M> {
+    type Target = PetriNet<Net, M>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.net
+    }
+}
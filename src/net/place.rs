@@ -7,7 +7,7 @@ use std::marker::PhantomData;
 use bevy_utils::StableHashMap;
 use educe::Educe;
 
-use super::NetId;
+use super::{short_type_name, NetId};
 
 /// Place belonging to a Petri net.
 ///
@@ -32,7 +32,7 @@ impl<Net: NetId> PlaceId<Net> {
     /// Usually, this value is taken from a counter incremented for each type of place registered with the Petri net.
     #[inline]
     #[must_use]
-    const fn new(index: usize) -> Self {
+    pub(crate) const fn new(index: usize) -> Self {
         Self(index, PhantomData)
     }
 
@@ -49,7 +49,10 @@ impl<Net: NetId> PlaceId<Net> {
 #[educe(Clone, Debug, Default)]
 pub struct PlaceMetadata<Net: NetId> {
     name: Cow<'static, str>,
+    /// Whether the name is the type name, as opposed to a display name.
+    is_type_name: bool,
     type_id: Option<TypeId>,
+    group: Option<Cow<'static, str>>,
     _net: PhantomData<Net>,
 }
 
@@ -59,7 +62,21 @@ impl<Net: NetId> PlaceMetadata<Net> {
     pub fn new<P: Place<Net>>() -> Self {
         Self {
             name: Cow::Borrowed(type_name::<P>()),
+            is_type_name: true,
             type_id: Some(TypeId::of::<P>()),
+            group: None,
+            _net: PhantomData,
+        }
+    }
+
+    /// Returns a new [`PlaceMetadata`] for the place `P` with a display name.
+    #[must_use]
+    pub fn new_named<P: Place<Net>, N: Into<Cow<'static, str>>>(name: N) -> Self {
+        Self {
+            name: name.into(),
+            is_type_name: false,
+            type_id: Some(TypeId::of::<P>()),
+            group: None,
             _net: PhantomData,
         }
     }
@@ -69,11 +86,37 @@ impl<Net: NetId> PlaceMetadata<Net> {
     pub fn new_anon<N: Into<Cow<'static, str>>>(name: N) -> Self {
         Self {
             name: name.into(),
+            is_type_name: false,
             type_id: None,
+            group: None,
             _net: PhantomData,
         }
     }
 
+    /// Returns a new [`PlaceMetadata`] for an "anonymous" place named after a Rust type,
+    /// such as a place of a deserialized net.
+    #[cfg(feature = "serde")]
+    pub(crate) fn new_anon_typed<N: Into<Cow<'static, str>>>(name: N) -> Self {
+        Self {
+            is_type_name: true,
+            ..Self::new_anon(name)
+        }
+    }
+
+    /// Returns the metadata with the place tagged with a group.
+    #[must_use]
+    pub fn with_group<G: Into<Cow<'static, str>>>(mut self, group: G) -> Self {
+        self.group = Some(group.into());
+        self
+    }
+
+    /// Returns the group the place is tagged with, if any.
+    #[inline]
+    #[must_use]
+    pub fn group(&self) -> Option<&str> {
+        self.group.as_deref()
+    }
+
     /// Returns the name of the place.
     #[inline]
     #[must_use]
@@ -81,6 +124,19 @@ impl<Net: NetId> PlaceMetadata<Net> {
         &self.name
     }
 
+    /// Returns the name of the place without its module path.
+    ///
+    /// For example, the short name of `my_game::net::Waiting` is `Waiting`.
+    /// Display names and the names of anonymous places are returned as they are.
+    #[must_use]
+    pub fn short_name(&self) -> &str {
+        if self.is_type_name {
+            short_type_name(&self.name)
+        } else {
+            &self.name
+        }
+    }
+
     /// Returns the [`TypeId`] of the place.
     ///
     /// ## Panics
@@ -104,7 +160,7 @@ impl<Net: NetId> PlaceMetadata<Net> {
 }
 
 #[derive(Educe)]
-#[educe(Debug, Default)]
+#[educe(Clone, Debug, Default)]
 pub(super) struct Places<Net: NetId> {
     places: Vec<PlaceMetadata<Net>>,
     indices: StableHashMap<TypeId, PlaceId<Net>>,
@@ -121,12 +177,46 @@ impl<Net: NetId> Places<Net> {
     /// Panics if a place of this type has already been initialized.
     #[inline]
     pub fn register<P: Place<Net>>(&mut self) -> PlaceId<Net> {
+        self.register_typed::<P>(PlaceMetadata::new::<P>())
+    }
+
+    /// Registers a place of type `P` with a display name with this instance.
+    ///
+    /// The returned `PlaceId` is specific to the Petri net instance
+    /// it was retrieved from and should not be used with another Petri net.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if a place of this type has already been initialized.
+    #[inline]
+    pub fn register_named<P: Place<Net>, N: Into<Cow<'static, str>>>(
+        &mut self,
+        name: N,
+    ) -> PlaceId<Net> {
+        self.register_typed::<P>(PlaceMetadata::new_named::<P, N>(name))
+    }
+
+    /// Registers a place of type `P` tagged with a group with this instance.
+    ///
+    /// The returned `PlaceId` is specific to the Petri net instance
+    /// it was retrieved from and should not be used with another Petri net.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the place has already been registered.
+    #[inline]
+    pub fn register_in_group<P: Place<Net>, G: Into<Cow<'static, str>>>(
+        &mut self,
+        group: G,
+    ) -> PlaceId<Net> {
+        self.register_typed::<P>(PlaceMetadata::new::<P>().with_group(group))
+    }
+
+    #[inline]
+    fn register_typed<P: Place<Net>>(&mut self, meta: PlaceMetadata<Net>) -> PlaceId<Net> {
         let Places { places, indices } = self;
         *indices
-            .try_insert(
-                TypeId::of::<P>(),
-                Self::init_inner(places, PlaceMetadata::new::<P>()),
-            )
+            .try_insert(TypeId::of::<P>(), Self::init_inner(places, meta))
             .unwrap_or_else(|_| panic!("Attempted to add a duplicate place: {}", type_name::<P>()))
     }
 
@@ -156,6 +246,12 @@ impl<Net: NetId> Places<Net> {
         self.places.len()
     }
 
+    /// Returns an iterator over the identifiers of all places registered with this instance.
+    #[inline]
+    pub fn ids(&self) -> impl Iterator<Item = PlaceId<Net>> {
+        (0..self.places.len()).map(PlaceId::new)
+    }
+
     /// Gets the metadata associated with the given place.
     #[inline]
     pub fn metadata(&self, id: PlaceId<Net>) -> &PlaceMetadata<Net> {
@@ -205,10 +301,48 @@ impl<Net: NetId> Places<Net> {
 
     /// Gets an iterator over all places registered with this instance.
     #[inline]
-    pub fn _iter(&self) -> impl Iterator<Item = &PlaceMetadata<Net>> + '_ {
-        self.places.iter()
+    pub fn iter(&self) -> impl Iterator<Item = (PlaceId<Net>, &PlaceMetadata<Net>)> + '_ {
+        self.places
+            .iter()
+            .enumerate()
+            .map(|(index, meta)| (PlaceId::new(index), meta))
     }
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use crate::{NetId, PetriNet, Place, PlaceMetadata};
+
+    enum N0 {}
+
+    mod nested {
+        pub enum Waiting {}
+    }
+
+    impl NetId for N0 {}
+    impl Place<N0> for nested::Waiting {}
+
+    #[test]
+    fn test_short_name_strips_module_path() {
+        let meta = PlaceMetadata::<N0>::new::<nested::Waiting>();
+        assert!(meta.name().ends_with("::nested::Waiting"));
+        assert_eq!(meta.short_name(), "Waiting");
+    }
+
+    #[test]
+    fn test_named_place_uses_display_name() {
+        let net = PetriNet::<N0>::new().add_place_named::<nested::Waiting, _>("Queue");
+        let (_, meta) = net.place::<nested::Waiting>();
+        assert_eq!(meta.name(), "Queue");
+        assert_eq!(meta.short_name(), "Queue");
+    }
+
+    #[test]
+    fn test_short_name_keeps_display_names() {
+        let mut net = PetriNet::<N0>::new().add_place_named::<nested::Waiting, _>("lobby::Queue");
+        let anon = net.add_place_anon("lobby::Exit");
+        let (_, meta) = net.place::<nested::Waiting>();
+        assert_eq!(meta.short_name(), "lobby::Queue");
+        assert_eq!(net.places.metadata(anon).short_name(), "lobby::Exit");
+    }
+}
@@ -0,0 +1,149 @@
+//! Serialization of Petri net definitions.
+
+use std::borrow::Cow;
+
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::place::{PlaceId, PlaceMetadata};
+use super::trans::{Inflow, Outflow, TransMetadata};
+use super::{Marks, NetId, PetriNet};
+
+/// Serialized form of a [`PetriNet`].
+///
+/// Places and transitions are referred to by their index in the net.
+#[derive(Serialize, Deserialize)]
+#[serde(bound(serialize = "M: Serialize", deserialize = "M: Deserialize<'de>"))]
+struct NetDef<'a, M> {
+    places: Vec<PlaceDef<'a, M>>,
+    transitions: Vec<TransDef<'a, M>>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(bound(serialize = "M: Serialize", deserialize = "M: Deserialize<'de>"))]
+struct PlaceDef<'a, M> {
+    name: Cow<'a, str>,
+    /// Whether the place was a Rust type.
+    typed: bool,
+    capacity: Option<M>,
+    #[serde(default)]
+    group: Option<Cow<'a, str>>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(bound(serialize = "M: Serialize", deserialize = "M: Deserialize<'de>"))]
+struct TransDef<'a, M> {
+    name: Cow<'a, str>,
+    /// Whether the transition was a Rust type.
+    typed: bool,
+    inflows: Vec<(usize, M)>,
+    outflows: Vec<(usize, M)>,
+}
+
+/// Serializes the places, transitions and arcs of the net.
+///
+/// Type identifiers cannot be serialized, so only the names of the nodes are kept,
+/// along with whether they were Rust types. Guards are not serialized.
+impl<Net: NetId, M: Marks + Serialize> Serialize for PetriNet<Net, M> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let places = self
+            .places()
+            .map(|(place, meta)| PlaceDef {
+                name: Cow::Borrowed(meta.name()),
+                typed: meta.get_type_id().is_some(),
+                capacity: self.capacity_by_id(place),
+                group: meta.group().map(Cow::Borrowed),
+            })
+            .collect();
+        let transitions = self
+            .transitions()
+            .map(|(trans, meta)| TransDef {
+                name: Cow::Borrowed(meta.name()),
+                typed: meta.get_type_id().is_some(),
+                inflows: self
+                    .flows
+                    .inflows(trans)
+                    .iter()
+                    .map(|&Inflow { source, weight }| (source.index(), weight))
+                    .collect(),
+                outflows: self
+                    .flows
+                    .outflows(trans)
+                    .iter()
+                    .map(|&Outflow { target, weight }| (target.index(), weight))
+                    .collect(),
+            })
+            .collect();
+        NetDef {
+            places,
+            transitions,
+        }
+        .serialize(serializer)
+    }
+}
+
+/// Deserializes a net in which every place and transition is anonymous.
+///
+/// The nodes keep their names, but cannot be looked up by their Rust types.
+/// The short names of the nodes that were Rust types still omit the module path.
+impl<'de, Net: NetId, M: Marks + Deserialize<'de>> Deserialize<'de> for PetriNet<Net, M> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let NetDef {
+            places,
+            transitions,
+        } = NetDef::<M>::deserialize(deserializer)?;
+        let mut net = PetriNet::new();
+        let place_count = places.len();
+        for PlaceDef {
+            name,
+            typed,
+            capacity,
+            group,
+        } in places
+        {
+            let mut meta = if typed {
+                PlaceMetadata::new_anon_typed(name.into_owned())
+            } else {
+                PlaceMetadata::new_anon(name.into_owned())
+            };
+            if let Some(group) = group {
+                meta = meta.with_group(group.into_owned());
+            }
+            let place = net.places.register_with_meta(meta);
+            if let Some(capacity) = capacity {
+                net.capacities.insert(place, capacity);
+            }
+        }
+        let to_arcs = |arcs: Vec<(usize, M)>| {
+            arcs.into_iter()
+                .map(|(index, weight)| {
+                    if index < place_count {
+                        Ok((PlaceId::new(index), weight))
+                    } else {
+                        Err(D::Error::custom(format_args!(
+                            "place index {index} is out of range"
+                        )))
+                    }
+                })
+                .collect::<Result<Vec<_>, _>>()
+        };
+        for TransDef {
+            name,
+            typed,
+            inflows,
+            outflows,
+        } in transitions
+        {
+            let inflows = to_arcs(inflows)?;
+            let outflows = to_arcs(outflows)?;
+            let meta = if typed {
+                TransMetadata::new_anon_typed(name.into_owned())
+            } else {
+                TransMetadata::new_anon(name.into_owned())
+            };
+            net.transitions.register_with_meta(meta);
+            net.add_arcs_dyn(&inflows, &outflows);
+        }
+        Ok(net)
+    }
+}
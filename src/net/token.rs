@@ -1,28 +1,213 @@
 //! Petri net token.
 
+use std::cmp::Ordering;
+#[cfg(feature = "sparse")]
+use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
 use std::marker::PhantomData;
 
-use bevy_ecs::component::Component;
+#[cfg(feature = "bevy")]
+use bevy_ecs::reflect::ReflectComponent;
+#[cfg(feature = "bevy")]
+use bevy_ecs::world::{FromWorld, World};
+#[cfg(all(feature = "bevy", feature = "serde"))]
+use bevy_reflect::{ReflectDeserialize, ReflectSerialize, TypePath, TypeRegistry};
 use educe::Educe;
+#[cfg(feature = "smallvec")]
+use smallvec::{smallvec, SmallVec};
 
 use super::place::PlaceId;
-use super::{NetId, NotEnoughMarks};
+use super::{DiffError, Marks, NetId, NotEnoughMarks, Overflow, PetriNet};
+
+/// Number of places whose marks are stored inline in a [`Token`].
+#[cfg(feature = "smallvec")]
+const INLINE_PLACES: usize = 8;
+
+/// Marks of every place of the net, indexed by [`PlaceId`].
+#[cfg(feature = "smallvec")]
+type Marking<M> = SmallVec<[M; INLINE_PLACES]>;
+
+/// Marks of every place of the net, indexed by [`PlaceId`].
+#[cfg(not(feature = "smallvec"))]
+type Marking<M> = Vec<M>;
+
+/// Storage of the marks of a [`Token`].
+///
+/// Tokens with different storage compare equal if they mark the same places the same number of times.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "bevy",
+    derive(bevy_reflect::Reflect),
+    reflect_value(PartialEq, Hash, Debug)
+)]
+enum Storage<M: Marks> {
+    /// Marks of every place.
+    Dense(Marking<M>),
+    /// Marks of the marked places only, and the number of places.
+    #[cfg(feature = "sparse")]
+    Sparse(BTreeMap<usize, M>, usize),
+}
+
+impl<M: Marks> Storage<M> {
+    fn len(&self) -> usize {
+        match self {
+            Storage::Dense(marking) => marking.len(),
+            #[cfg(feature = "sparse")]
+            Storage::Sparse(_, len) => *len,
+        }
+    }
+
+    /// Returns the marks of the place at `index`, or zero if it is out of range.
+    fn get(&self, index: usize) -> M {
+        match self {
+            Storage::Dense(marking) => marking.get(index).copied().unwrap_or_default(),
+            #[cfg(feature = "sparse")]
+            Storage::Sparse(marking, _) => marking.get(&index).copied().unwrap_or_default(),
+        }
+    }
+
+    fn set(&mut self, index: usize, marks: M) {
+        match self {
+            Storage::Dense(marking) => marking[index] = marks,
+            #[cfg(feature = "sparse")]
+            Storage::Sparse(marking, _) => {
+                if marks == M::default() {
+                    marking.remove(&index);
+                } else {
+                    marking.insert(index, marks);
+                }
+            }
+        }
+    }
+
+    fn sum(&self) -> M {
+        match self {
+            Storage::Dense(marking) => marking.iter().copied().sum(),
+            #[cfg(feature = "sparse")]
+            Storage::Sparse(marking, _) => marking.values().copied().sum(),
+        }
+    }
+
+    fn checked_sum(&self) -> Option<M> {
+        match self {
+            Storage::Dense(marking) => marking
+                .iter()
+                .try_fold(M::default(), |sum, &marks| sum.checked_add(marks)),
+            #[cfg(feature = "sparse")]
+            Storage::Sparse(marking, _) => marking
+                .values()
+                .try_fold(M::default(), |sum, &marks| sum.checked_add(marks)),
+        }
+    }
+
+    fn clear(&mut self) {
+        match self {
+            Storage::Dense(marking) => marking.fill(M::default()),
+            #[cfg(feature = "sparse")]
+            Storage::Sparse(marking, _) => marking.clear(),
+        }
+    }
+
+    fn resize(&mut self, len: usize) {
+        match self {
+            Storage::Dense(marking) => marking.resize(len, M::default()),
+            #[cfg(feature = "sparse")]
+            Storage::Sparse(marking, old_len) => {
+                marking.split_off(&len);
+                *old_len = len;
+            }
+        }
+    }
+
+    /// Returns the marks of every place, in order.
+    fn iter(&self) -> impl Iterator<Item = M> + '_ {
+        (0..self.len()).map(|index| self.get(index))
+    }
+}
+
+impl<M: Marks> PartialEq for Storage<M> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Storage::Dense(lhs), Storage::Dense(rhs)) => lhs == rhs,
+            #[cfg(feature = "sparse")]
+            (Storage::Sparse(lhs, lhs_len), Storage::Sparse(rhs, rhs_len)) => {
+                lhs_len == rhs_len && lhs == rhs
+            }
+            #[cfg(feature = "sparse")]
+            _ => self.len() == other.len() && self.iter().eq(other.iter()),
+        }
+    }
+}
+
+impl<M: Marks> Eq for Storage<M> {}
+
+impl<M: Marks> PartialOrd for Storage<M> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<M: Marks> Ord for Storage<M> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Storage::Dense(lhs), Storage::Dense(rhs)) => lhs.cmp(rhs),
+            #[cfg(feature = "sparse")]
+            _ => self.iter().cmp(other.iter()),
+        }
+    }
+}
+
+impl<M: Marks> Hash for Storage<M> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.len());
+        for marks in self.iter() {
+            marks.hash(state);
+        }
+    }
+}
 
 /// Petri net token. Holds the state of the net execution.
 ///
+/// With the `smallvec` feature enabled, the marking of a net with at most
+/// eight places is stored inline, without allocating.
+/// With the `sparse` feature enabled, tokens spawned with [`PetriNet::spawn_sparse_token`]
+/// only store the marks of the marked places, which saves memory in large nets.
+/// With the `bevy` feature enabled, tokens of nets that implement `TypePath` can be reflected,
+/// with their marking as an opaque value. With the `serde` feature also enabled,
+/// the marking can be serialized, so that tokens can be saved in scenes.
+///
 // TODO: WorldQuery for querying tokens with a specific marking
-#[derive(Component, Educe)]
+#[derive(Educe)]
+#[cfg_attr(
+    feature = "bevy",
+    derive(bevy_ecs::component::Component, bevy_reflect::Reflect),
+    reflect(Component)
+)]
 #[educe(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
-pub struct Token<Net: NetId> {
-    marking: Vec<usize>,
+pub struct Token<Net: NetId, M: Marks = usize> {
+    marking: Storage<M>,
+    #[cfg_attr(feature = "bevy", reflect(ignore))]
     _net: PhantomData<Net>,
 }
 
-impl<Net: NetId> Token<Net> {
+impl<Net: NetId, M: Marks> Token<Net, M> {
     /// Returns a new token.
     pub(super) fn new(num_places: usize) -> Self {
         Self {
-            marking: vec![0; num_places],
+            #[cfg(feature = "smallvec")]
+            marking: Storage::Dense(smallvec![M::default(); num_places]),
+            #[cfg(not(feature = "smallvec"))]
+            marking: Storage::Dense(vec![M::default(); num_places]),
+            _net: PhantomData,
+        }
+    }
+
+    /// Returns a new token that only stores the marks of the marked places.
+    #[cfg(feature = "sparse")]
+    pub(super) fn new_sparse(num_places: usize) -> Self {
+        Self {
+            marking: Storage::Sparse(BTreeMap::new(), num_places),
             _net: PhantomData,
         }
     }
@@ -30,25 +215,165 @@ impl<Net: NetId> Token<Net> {
     /// Returns the total number of markings by a token.
     #[inline]
     #[must_use]
-    pub fn total_marks(&self) -> usize {
-        self.marking.iter().sum()
+    pub fn total_marks(&self) -> M {
+        self.marking.sum()
+    }
+
+    /// Returns the total number of markings by a token, or `None` if it does not fit in `M`.
+    #[inline]
+    #[must_use]
+    pub fn total_marks_checked(&self) -> Option<M> {
+        self.marking.checked_sum()
+    }
+
+    /// Returns the marks of every place, indexed by [`PlaceId::index`],
+    /// or `None` if the token only stores the marks of the marked places.
+    ///
+    /// The order matches the order of [`PetriNet::places`].
+    /// See [`Token::to_marking`] for a copy of the marks of any token.
+    #[inline]
+    #[must_use]
+    pub fn as_slice(&self) -> Option<&[M]> {
+        match &self.marking {
+            Storage::Dense(marking) => Some(marking.as_slice()),
+            #[cfg(feature = "sparse")]
+            Storage::Sparse(..) => None,
+        }
+    }
+
+    /// Returns a copy of the marks of every place, indexed by [`PlaceId::index`].
+    ///
+    /// The order matches the order of [`PetriNet::places`].
+    #[must_use]
+    pub fn to_marking(&self) -> Vec<M> {
+        self.marking.iter().collect()
+    }
+
+    /// Removes all markings, keeping the token sized for its net.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.marking.clear();
+    }
+
+    /// Resizes the token to fit all places of the net,
+    /// leaving places added after the token was spawned unmarked.
+    pub fn resize_for(&mut self, net: &PetriNet<Net, M>) {
+        self.marking.resize(net.places.len());
+    }
+
+    /// Returns the signed difference in marks (`self - other`) for every place where they differ.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if a difference does not fit in an `i64`.
+    #[must_use]
+    pub fn diff(&self, other: &Token<Net, M>) -> Vec<(PlaceId<Net>, i64)>
+    where
+        M: TryInto<i64>,
+    {
+        let len = self.marking.len().max(other.marking.len());
+        (0..len)
+            .filter_map(|index| {
+                let lhs = self.marking.get(index);
+                let rhs = other.marking.get(index);
+                let delta = match lhs.cmp(&rhs) {
+                    Ordering::Equal => return None,
+                    Ordering::Greater => to_i64(lhs - rhs),
+                    Ordering::Less => -to_i64(rhs - lhs),
+                };
+                Some((PlaceId::new(index), delta))
+            })
+            .collect()
+    }
+
+    /// Applies a difference produced by [`Token::diff`] to this token.
+    ///
+    /// The difference is either applied as a whole, or not at all.
+    ///
+    /// ## Errors
+    ///
+    /// Returns a [`DiffError`] if a place is out of range for the token,
+    /// if a place would end up with a negative number of marks,
+    /// or if the number of marks would overflow `M`. The token is left untouched if so.
+    pub fn apply_diff(&mut self, diff: &[(PlaceId<Net>, i64)]) -> Result<(), DiffError<Net>>
+    where
+        M: TryFrom<u64>,
+    {
+        let mut next = self.clone();
+        for &(place, delta) in diff {
+            if place.index() >= next.marking.len() {
+                return Err(DiffError::OutOfRange(place));
+            }
+            if delta < 0 {
+                let n = M::try_from(delta.unsigned_abs()).map_err(|_| NotEnoughMarks(place))?;
+                next.unmark_by_id(place, n)?;
+            } else {
+                let n = M::try_from(delta.unsigned_abs()).map_err(|_| Overflow(place))?;
+                next.mark_checked_by_id(place, n)?;
+            }
+        }
+        *self = next;
+        Ok(())
+    }
+
+    /// Returns the position of the place in the marking.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the place was added to the net after the token was spawned.
+    fn slot(&self, place: PlaceId<Net>) -> usize {
+        let index = place.index();
+        assert!(
+            index < self.marking.len(),
+            "Place {:?} is out of range for a token with {} places. \
+             Call `Token::resize_for` after adding places to the net.",
+            place,
+            self.marking.len()
+        );
+        index
     }
 
-    pub(super) fn marks_by_id(&self, place: PlaceId<Net>) -> usize {
-        self.marking[place.index()]
+    pub(super) fn marks_by_id(&self, place: PlaceId<Net>) -> M {
+        self.marking.get(self.slot(place))
     }
 
-    pub(super) fn mark_by_id(&mut self, place: PlaceId<Net>, n: usize) {
-        self.marking[place.index()] += n;
+    pub(super) fn mark_by_id(&mut self, place: PlaceId<Net>, n: M) {
+        self.mark_checked_by_id(place, n)
+            .unwrap_or_else(|err| panic!("{err}"));
+    }
+
+    pub(super) fn set_marks_by_id(&mut self, place: PlaceId<Net>, n: M) {
+        let slot = self.slot(place);
+        self.marking.set(slot, n);
+    }
+
+    pub(super) fn mark_checked_by_id(
+        &mut self,
+        place: PlaceId<Net>,
+        n: M,
+    ) -> Result<(), Overflow<Net>> {
+        let slot = self.slot(place);
+        let marks = self.marking.get(slot);
+        self.marking
+            .set(slot, marks.checked_add(n).ok_or(Overflow(place))?);
+        Ok(())
+    }
+
+    pub(super) fn mark_saturating_by_id(&mut self, place: PlaceId<Net>, n: M) {
+        let slot = self.slot(place);
+        let marks = self.marking.get(slot);
+        self.marking.set(slot, marks.saturating_add(n));
     }
 
     pub(super) fn unmark_by_id(
         &mut self,
         place: PlaceId<Net>,
-        n: usize,
+        n: M,
     ) -> Result<(), NotEnoughMarks<Net>> {
-        if self.marking[place.index()] >= n {
-            self.marking[place.index()] -= n;
+        let slot = self.slot(place);
+        let marks = self.marking.get(slot);
+        if marks >= n {
+            self.marking.set(slot, marks - n);
             Ok(())
         } else {
             Err(NotEnoughMarks(place))
@@ -56,16 +381,106 @@ impl<Net: NetId> Token<Net> {
     }
 }
 
+/// Colored Petri net token. Holds the state of the net execution,
+/// where each mark carries a value of type `C`.
+///
+/// Values are consumed from a place in the order they were marked (oldest first).
+#[derive(Educe)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::component::Component))]
+#[educe(
+    Clone(bound(C: Clone)),
+    Eq,
+    PartialEq(bound(C: PartialEq)),
+    Hash(bound(C: std::hash::Hash)),
+    Debug(bound(C: std::fmt::Debug))
+)]
+pub struct ColoredToken<Net: NetId, C: Send + Sync + 'static> {
+    marking: Vec<Vec<C>>,
+    _net: PhantomData<Net>,
+}
+
+impl<Net: NetId, C: Send + Sync + 'static> ColoredToken<Net, C> {
+    /// Returns a new colored token.
+    pub(super) fn new(num_places: usize) -> Self {
+        Self {
+            marking: (0..num_places).map(|_| Vec::new()).collect(),
+            _net: PhantomData,
+        }
+    }
+
+    /// Returns the total number of markings by a token.
+    #[inline]
+    #[must_use]
+    pub fn total_marks(&self) -> usize {
+        self.marking.iter().map(Vec::len).sum()
+    }
+
+    pub(super) fn marks_by_id(&self, place: PlaceId<Net>) -> &[C] {
+        &self.marking[place.index()]
+    }
+
+    pub(super) fn mark_by_id(&mut self, place: PlaceId<Net>, value: C) {
+        self.marking[place.index()].push(value);
+    }
+
+    pub(super) fn unmark_by_id(
+        &mut self,
+        place: PlaceId<Net>,
+        n: usize,
+    ) -> Result<impl Iterator<Item = C> + '_, NotEnoughMarks<Net>> {
+        let values = &mut self.marking[place.index()];
+        if values.len() >= n {
+            Ok(values.drain(..n))
+        } else {
+            Err(NotEnoughMarks(place))
+        }
+    }
+}
+
+fn to_i64<M: Marks + TryInto<i64>>(n: M) -> i64 {
+    n.try_into()
+        .unwrap_or_else(|_| panic!("Difference of {n} marks does not fit in `i64`."))
+}
+
+/// Spawns a token of the [`PetriNet`] resource, so that [`ReflectComponent`] can insert
+/// a token before applying the reflected marking.
+///
+/// The reflected marking replaces the whole marking, so if the net is not a resource,
+/// e.g. when it is labeled, a token without places is inserted instead.
+#[cfg(feature = "bevy")]
+impl<Net: NetId, M: Marks> FromWorld for Token<Net, M> {
+    fn from_world(world: &mut World) -> Self {
+        world
+            .get_resource::<PetriNet<Net, M>>()
+            .map_or_else(|| Self::new(0), PetriNet::spawn_token)
+    }
+}
+
+#[cfg(all(feature = "bevy", feature = "serde"))]
+impl<Net: NetId + TypePath, M> Token<Net, M>
+where
+    M: Marks + TypePath + serde::Serialize + serde::de::DeserializeOwned,
+{
+    /// Registers the serialization of the marking, so that tokens can be saved in scenes.
+    pub(crate) fn register_serde(registry: &mut TypeRegistry) {
+        registry.register::<Storage<M>>();
+        registry.register_type_data::<Storage<M>, ReflectSerialize>();
+        registry.register_type_data::<Storage<M>, ReflectDeserialize>();
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{NetId, PetriNet, Place, Trans, W};
 
     enum N0 {}
     enum P0 {}
+    enum P1 {}
     enum T0 {}
 
     impl NetId for N0 {}
     impl Place<N0> for P0 {}
+    impl Place<N0> for P1 {}
     impl Trans<N0> for T0 {}
 
     const N: usize = 3;
@@ -111,4 +526,117 @@ mod tests {
         net.mark::<P0>(&mut token, N);
         assert!(net.unmark::<P0>(&mut token, N + 1).is_err());
     }
+
+    #[test]
+    fn test_cleared_token_has_no_markings() {
+        let net = net();
+        let mut token = net.spawn_token();
+        net.mark::<P0>(&mut token, N);
+        net.reset_token(&mut token);
+        assert_eq!(token.total_marks(), 0);
+        assert_eq!(token.marking.len(), net.places().count());
+    }
+
+    #[test]
+    fn test_checked_total_marks_detects_overflow() {
+        let net = net().add_place::<P1>();
+        let mut token = net.spawn_token();
+        net.mark::<P0>(&mut token, usize::MAX - 1);
+        assert_eq!(token.total_marks_checked(), Some(usize::MAX - 1));
+        net.mark::<P1>(&mut token, 1);
+        assert_eq!(token.total_marks_checked(), Some(usize::MAX));
+        net.mark::<P1>(&mut token, 1);
+        assert_eq!(token.total_marks_checked(), None);
+    }
+
+    #[test]
+    fn test_resized_token_fits_new_places() {
+        let mut net = net();
+        let mut token = net.spawn_token();
+        net = net.add_place::<P1>();
+        token.resize_for(&net);
+        net.mark::<P1>(&mut token, N);
+        assert_eq!(net.marks::<P1>(&token), N);
+    }
+
+    #[test]
+    #[should_panic(expected = "out of range")]
+    fn test_marking_a_place_added_after_spawning_panics() {
+        let mut net = net();
+        let mut token = net.spawn_token();
+        net = net.add_place::<P1>();
+        net.mark::<P1>(&mut token, N);
+    }
+
+    #[test]
+    fn test_marking_past_max_fails() {
+        let net = net();
+        let mut token = net.spawn_token();
+        net.mark::<P0>(&mut token, usize::MAX - 1);
+        assert!(net.mark_checked::<P0>(&mut token, N).is_err());
+        assert_eq!(net.marks::<P0>(&token), usize::MAX - 1);
+        assert!(net.mark_checked::<P0>(&mut token, 1).is_ok());
+        assert_eq!(net.marks::<P0>(&token), usize::MAX);
+    }
+
+    #[test]
+    fn test_saturating_marking_stops_at_max() {
+        let net = net();
+        let mut token = net.spawn_token();
+        net.mark::<P0>(&mut token, usize::MAX - 1);
+        net.mark_saturating::<P0>(&mut token, N);
+        assert_eq!(net.marks::<P0>(&token), usize::MAX);
+    }
+
+    #[test]
+    #[should_panic(expected = "would overflow")]
+    fn test_marking_past_max_panics() {
+        let net = net();
+        let mut token = net.spawn_token();
+        net.mark::<P0>(&mut token, usize::MAX);
+        net.mark::<P0>(&mut token, 1);
+    }
+
+    #[cfg(feature = "smallvec")]
+    #[test]
+    fn test_small_net_marking_is_inline() {
+        let net = net();
+        let mut token = net.spawn_token();
+        net.mark::<P0>(&mut token, N);
+        assert!(net.fire::<T0>(&mut token).is_ok());
+        assert_eq!(net.marks::<P0>(&token), N - 1);
+        assert!(matches!(
+            &token.marking,
+            super::Storage::Dense(marking) if !marking.spilled()
+        ));
+    }
+
+    #[test]
+    fn test_marking_slice_matches_places() {
+        let net = net();
+        let mut token = net.spawn_token();
+        net.mark::<P0>(&mut token, N);
+        let marking = token.as_slice().unwrap();
+        assert_eq!(marking.len(), net.places().count());
+        assert_eq!(marking, token.to_marking());
+        for (place, _) in net.places() {
+            assert_eq!(marking[place.index()], net.marks_by_id(place, &token));
+        }
+    }
+
+    #[test]
+    fn test_colored_token_keeps_marking_order() {
+        let net = PetriNet::<N0>::new()
+            .add_place::<P0>()
+            .add_place::<P1>()
+            .add_trans::<T0, (P0, W<1>), (P1, W<1>)>();
+        let mut token = net.spawn_colored_token();
+        net.mark_colored::<P0, _>(&mut token, 'a');
+        net.mark_colored::<P0, _>(&mut token, 'b');
+        assert_eq!(token.total_marks(), 2);
+        assert_eq!(net.colored_marks::<P0, _>(&token), ['a', 'b']);
+        assert!(net.fire_colored::<T0, _>(&mut token).is_ok());
+        assert_eq!(net.colored_marks::<P0, _>(&token), ['b']);
+        assert_eq!(net.colored_marks::<P1, _>(&token), ['a']);
+    }
 }
@@ -8,7 +8,7 @@ use bevy_utils::StableHashMap;
 use educe::Educe;
 
 use super::place::PlaceId;
-use super::NetId;
+use super::{short_type_name, Marks, NetId};
 
 /// Transition belonging to a Petri net.
 pub trait Trans<Net: NetId>: Send + Sync + 'static {}
@@ -40,11 +40,29 @@ impl<Net: NetId> TransId<Net> {
     }
 }
 
+/// [`TransId`] of a [`Trans`] resolved once, for firing it repeatedly without looking it up.
+///
+/// Obtained with [`PetriNet::handle`](super::PetriNet::handle).
+#[derive(Educe)]
+#[educe(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct TransHandle<Net: NetId>(pub(super) TransId<Net>);
+
+impl<Net: NetId> TransHandle<Net> {
+    /// Returns the identifier of the transition.
+    #[inline]
+    #[must_use]
+    pub const fn id(self) -> TransId<Net> {
+        self.0
+    }
+}
+
 /// A value describing a [`Trans`], which may or may not be a Rust type.
 #[derive(Educe)]
-#[educe(Debug, Default)]
+#[educe(Clone, Debug, Default)]
 pub struct TransMetadata<Net: NetId> {
     name: Cow<'static, str>,
+    /// Whether the name is the type name, as opposed to a display name.
+    is_type_name: bool,
     type_id: Option<TypeId>,
     _net: PhantomData<Net>,
 }
@@ -55,6 +73,18 @@ impl<Net: NetId> TransMetadata<Net> {
     pub fn new<T: Trans<Net>>() -> Self {
         Self {
             name: Cow::Borrowed(type_name::<T>()),
+            is_type_name: true,
+            type_id: Some(TypeId::of::<T>()),
+            _net: PhantomData,
+        }
+    }
+
+    /// Returns a new [`TransMetadata`] for the transition `T` with a display name.
+    #[must_use]
+    pub fn new_named<T: Trans<Net>, N: Into<Cow<'static, str>>>(name: N) -> Self {
+        Self {
+            name: name.into(),
+            is_type_name: false,
             type_id: Some(TypeId::of::<T>()),
             _net: PhantomData,
         }
@@ -65,11 +95,22 @@ impl<Net: NetId> TransMetadata<Net> {
     pub fn new_anon<N: Into<Cow<'static, str>>>(name: N) -> Self {
         Self {
             name: name.into(),
+            is_type_name: false,
             type_id: None,
             _net: PhantomData,
         }
     }
 
+    /// Returns a new [`TransMetadata`] for an "anonymous" transition named after a Rust type,
+    /// such as a transition of a deserialized net.
+    #[cfg(feature = "serde")]
+    pub(crate) fn new_anon_typed<N: Into<Cow<'static, str>>>(name: N) -> Self {
+        Self {
+            is_type_name: true,
+            ..Self::new_anon(name)
+        }
+    }
+
     /// Returns the name of the transition.
     #[inline]
     #[must_use]
@@ -77,6 +118,19 @@ impl<Net: NetId> TransMetadata<Net> {
         &self.name
     }
 
+    /// Returns the name of the transition without its module path.
+    ///
+    /// For example, the short name of `my_game::net::Take<2>` is `Take<2>`.
+    /// Display names and the names of anonymous transitions are returned as they are.
+    #[must_use]
+    pub fn short_name(&self) -> &str {
+        if self.is_type_name {
+            short_type_name(&self.name)
+        } else {
+            &self.name
+        }
+    }
+
     /// Returns the [`TypeId`] of the transition.
     ///
     /// ## Panics
@@ -100,7 +154,7 @@ impl<Net: NetId> TransMetadata<Net> {
 }
 
 #[derive(Educe)]
-#[educe(Debug, Default)]
+#[educe(Clone, Debug, Default)]
 pub(super) struct Transitions<Net: NetId> {
     transitions: Vec<TransMetadata<Net>>,
     indices: StableHashMap<TypeId, TransId<Net>>,
@@ -117,15 +171,33 @@ impl<Net: NetId> Transitions<Net> {
     /// Panics if a transition of this type has already been initialized.
     #[inline]
     pub fn register<T: Trans<Net>>(&mut self) -> TransId<Net> {
+        self.register_typed::<T>(TransMetadata::new::<T>())
+    }
+
+    /// Registers a transition of type `T` with a display name with the Petri net.
+    ///
+    /// The returned `TransId` is specific to the Petri net instance
+    /// it was retrieved from and should not be used with another Petri net.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if a transition of this type has already been initialized.
+    #[inline]
+    pub fn register_named<T: Trans<Net>, N: Into<Cow<'static, str>>>(
+        &mut self,
+        name: N,
+    ) -> TransId<Net> {
+        self.register_typed::<T>(TransMetadata::new_named::<T, N>(name))
+    }
+
+    #[inline]
+    fn register_typed<T: Trans<Net>>(&mut self, meta: TransMetadata<Net>) -> TransId<Net> {
         let Transitions {
             transitions,
             indices,
         } = self;
         *indices
-            .try_insert(
-                TypeId::of::<T>(),
-                Self::init_inner(transitions, TransMetadata::new::<T>()),
-            )
+            .try_insert(TypeId::of::<T>(), Self::init_inner(transitions, meta))
             .unwrap_or_else(|_| {
                 panic!(
                     "Attempted to add a duplicate transition: {}",
@@ -157,6 +229,12 @@ impl<Net: NetId> Transitions<Net> {
         index
     }
 
+    /// Returns an iterator over the identifiers of all transitions registered with the Petri net.
+    #[inline]
+    pub fn ids(&self) -> impl Iterator<Item = TransId<Net>> {
+        (0..self.transitions.len()).map(TransId::new)
+    }
+
     /// Returns the metadata associated with the given transition.
     #[inline]
     pub fn metadata(&self, id: TransId<Net>) -> &TransMetadata<Net> {
@@ -212,49 +290,112 @@ impl<Net: NetId> Transitions<Net> {
 
     /// Gets an iterator over all transition metadata registered with the Petri net.
     #[inline]
-    pub fn _iter(&self) -> impl Iterator<Item = &TransMetadata<Net>> + '_ {
-        self.transitions.iter()
+    pub fn iter(&self) -> impl Iterator<Item = (TransId<Net>, &TransMetadata<Net>)> + '_ {
+        self.transitions
+            .iter()
+            .enumerate()
+            .map(|(index, meta)| (TransId::new(index), meta))
     }
 }
 
 #[derive(Educe)]
-#[educe(Debug, Default)]
-pub(crate) struct Inflow<Net: NetId> {
+#[educe(Copy, Clone, Debug, Default)]
+pub(crate) struct Inflow<Net: NetId, M: Marks> {
     pub source: PlaceId<Net>,
-    pub weight: usize,
+    pub weight: M,
 }
 
 #[derive(Educe)]
-#[educe(Debug, Default)]
-pub(crate) struct Outflow<Net: NetId> {
+#[educe(Copy, Clone, Debug, Default)]
+pub(crate) struct Outflow<Net: NetId, M: Marks> {
     pub target: PlaceId<Net>,
-    pub weight: usize,
+    pub weight: M,
 }
 
+/// Inflows and outflows of each transition.
+///
+/// Arcs between the same transition and place are merged into one,
+/// so that each place is checked against the total weight when firing.
 #[derive(Educe)]
-#[educe(Debug, Default)]
-pub(crate) struct Flows<Net: NetId> {
-    inflows: Vec<Vec<Inflow<Net>>>,
-    outflows: Vec<Vec<Outflow<Net>>>,
+#[educe(Clone, Debug, Default)]
+pub(crate) struct Flows<Net: NetId, M: Marks> {
+    inflows: Vec<Vec<Inflow<Net, M>>>,
+    outflows: Vec<Vec<Outflow<Net, M>>>,
 }
 
-impl<Net: NetId> Flows<Net> {
-    pub fn add_inflows(&mut self, inflows: Vec<Inflow<Net>>) {
-        self.inflows.push(inflows);
+impl<Net: NetId, M: Marks> Flows<Net, M> {
+    pub fn add_inflows(&mut self, inflows: Vec<Inflow<Net, M>>) {
+        self.inflows.push(Vec::with_capacity(inflows.len()));
+        let trans = TransId::new(self.inflows.len() - 1);
+        for inflow in inflows {
+            self.push_inflow(trans, inflow);
+        }
+    }
+
+    pub fn add_outflows(&mut self, outflows: Vec<Outflow<Net, M>>) {
+        self.outflows.push(Vec::with_capacity(outflows.len()));
+        let trans = TransId::new(self.outflows.len() - 1);
+        for outflow in outflows {
+            self.push_outflow(trans, outflow);
+        }
     }
 
-    pub fn add_outflows(&mut self, outflows: Vec<Outflow<Net>>) {
-        self.outflows.push(outflows);
+    pub fn len(&self) -> usize {
+        self.inflows.len()
     }
 
-    pub fn inflows(&self, trans: TransId<Net>) -> &[Inflow<Net>] {
+    pub fn push_inflow(&mut self, trans: TransId<Net>, inflow: Inflow<Net, M>) {
+        let inflows = &mut self.inflows[trans.index()];
+        match inflows.iter_mut().find(|arc| arc.source == inflow.source) {
+            Some(arc) => arc.weight += inflow.weight,
+            None => inflows.push(inflow),
+        }
+    }
+
+    pub fn push_outflow(&mut self, trans: TransId<Net>, outflow: Outflow<Net, M>) {
+        let outflows = &mut self.outflows[trans.index()];
+        match outflows.iter_mut().find(|arc| arc.target == outflow.target) {
+            Some(arc) => arc.weight += outflow.weight,
+            None => outflows.push(outflow),
+        }
+    }
+
+    pub fn inflows(&self, trans: TransId<Net>) -> &[Inflow<Net, M>] {
         &self.inflows[trans.index()]
     }
 
-    pub fn outflows(&self, trans: TransId<Net>) -> &[Outflow<Net>] {
+    pub fn outflows(&self, trans: TransId<Net>) -> &[Outflow<Net, M>] {
         &self.outflows[trans.index()]
     }
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use crate::{NetId, PetriNet, Place, Trans, TransMetadata, W};
+
+    enum N0 {}
+    enum P0 {}
+
+    mod nested {
+        pub enum Take<const N: usize> {}
+    }
+
+    impl NetId for N0 {}
+    impl Place<N0> for P0 {}
+    impl<const N: usize> Trans<N0> for nested::Take<N> {}
+
+    #[test]
+    fn test_short_name_keeps_generics() {
+        let meta = TransMetadata::<N0>::new::<nested::Take<0>>();
+        assert_eq!(meta.short_name(), "Take<0>");
+    }
+
+    #[test]
+    fn test_named_trans_uses_display_name() {
+        let net = PetriNet::<N0>::new()
+            .add_place::<P0>()
+            .add_trans_named::<nested::Take<0>, (P0, W<1>), (), _>("Take");
+        let (_, meta) = net.trans::<nested::Take<0>>();
+        assert_eq!(meta.name(), "Take");
+    }
+}
@@ -1,19 +1,590 @@
 //! Bevy plugin.
 
-use bevy_app::{App, Plugin};
+use std::borrow::Cow;
+use std::convert::Infallible;
+use std::marker::PhantomData;
 
-use crate::net::{NetId, PetriNet};
+use bevy_app::{App, Plugin, Startup, Update};
+use bevy_ecs::component::Component;
+#[cfg(feature = "serde")]
+use bevy_ecs::reflect::AppTypeRegistry;
+use bevy_ecs::schedule::{apply_deferred, IntoSystemConfigs, IntoSystemSetConfigs};
+use bevy_ecs::schedule::{ScheduleLabel, SystemSet};
+use bevy_ecs::system::{Commands, Res, Resource};
+use bevy_reflect::TypePath;
+use bevy_utils::StableHashMap;
+use educe::Educe;
+#[cfg(feature = "serde")]
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::net::token::Token;
+use crate::net::trans::Trans;
+use crate::net::{FireError, Marks, NetId, PetriNet};
 
 /// Plugin that initializes and manages a [`PetriNet`].
-pub struct PetriNetPlugin<Net: NetId> {
-    /// Function used to build the [`PetriNet`].
-    /// FIXME: feels clunky?
-    pub build: fn(PetriNet<Net>) -> PetriNet<Net>,
+///
+/// The [`Token`]s of the net are not registered for reflection,
+/// see [`PetriNetAppExt::register_token`].
+pub struct PetriNetPlugin<Net: NetId, M: Marks = usize> {
+    build: Box<NetBuild<Net, M>>,
+    initial_token: Option<TokenInit<Net, M>>,
+    label: Option<Cow<'static, str>>,
+}
+
+/// Function used to build a [`PetriNet`].
+type NetBuild<Net, M> = dyn Fn(PetriNet<Net, M>) -> PetriNet<Net, M> + Send + Sync;
+
+/// Function used to mark a freshly spawned [`Token`].
+type TokenInit<Net, M> = fn(&PetriNet<Net, M>, &mut Token<Net, M>);
+
+impl<Net: NetId, M: Marks> PetriNetPlugin<Net, M> {
+    /// Returns a plugin that builds the [`PetriNet`] with the given function.
+    ///
+    /// The function may be a plain `fn` or a closure capturing its environment,
+    /// e.g. configuration loaded at runtime.
+    pub fn new(
+        build: impl Fn(PetriNet<Net, M>) -> PetriNet<Net, M> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            build: Box::new(build),
+            initial_token: None,
+            label: None,
+        }
+    }
+
+    /// Sets the function used to mark the initial [`Token`].
+    ///
+    /// If set, a single token entity is spawned at [`Startup`] and marked by this function.
+    /// Otherwise, tokens have to be spawned manually.
+    #[must_use]
+    pub fn with_initial_token(mut self, init: fn(&PetriNet<Net, M>, &mut Token<Net, M>)) -> Self {
+        self.initial_token = Some(init);
+        self
+    }
+
+    /// Sets the label of the net instance.
+    ///
+    /// Labeled nets are stored in the [`PetriNets`] resource instead of the [`PetriNet`] resource,
+    /// which allows adding several instances of the same net with different labels.
+    /// The initial token of a labeled net is spawned with a [`NetLabel`].
+    #[must_use]
+    pub fn with_label<L: Into<Cow<'static, str>>>(mut self, label: L) -> Self {
+        self.label = Some(label.into());
+        self
+    }
 }
 
-impl<Net: NetId> Plugin for PetriNetPlugin<Net> {
+impl<Net: NetId, M: Marks> From<fn(PetriNet<Net, M>) -> PetriNet<Net, M>>
+    for PetriNetPlugin<Net, M>
+{
+    fn from(build: fn(PetriNet<Net, M>) -> PetriNet<Net, M>) -> Self {
+        Self::new(build)
+    }
+}
+
+impl<Net: NetId, M: Marks> Plugin for PetriNetPlugin<Net, M> {
     fn build(&self, app: &mut App) {
         let pnet = (self.build)(PetriNet::new());
-        app.insert_resource(pnet);
+        let init = self.initial_token;
+        if let Some(label) = self.label.clone() {
+            app.init_resource::<PetriNets<Net, M>>()
+                .world
+                .resource_mut::<PetriNets<Net, M>>()
+                .insert(label.clone(), pnet);
+            if let Some(init) = init {
+                app.add_systems(
+                    Startup,
+                    move |mut commands: Commands, nets: Res<PetriNets<Net, M>>| {
+                        let net = nets.get(&label);
+                        let mut token = net.spawn_token();
+                        init(net, &mut token);
+                        commands.spawn((token, NetLabel::<Net>::new(label.clone())));
+                    },
+                );
+            }
+        } else {
+            app.insert_resource(pnet);
+            if let Some(init) = init {
+                app.add_systems(
+                    Startup,
+                    move |mut commands: Commands, net: Res<PetriNet<Net, M>>| {
+                        let mut token = net.spawn_token();
+                        init(&net, &mut token);
+                        commands.spawn(token);
+                    },
+                );
+            }
+        }
+    }
+
+    fn is_unique(&self) -> bool {
+        self.label.is_none()
+    }
+}
+
+/// Resource holding several labeled instances of a [`PetriNet`].
+///
+/// Populated by [`PetriNetPlugin::with_label`].
+#[derive(Resource, Educe)]
+#[educe(Debug, Default)]
+pub struct PetriNets<Net: NetId, M: Marks = usize> {
+    nets: StableHashMap<Cow<'static, str>, PetriNet<Net, M>>,
+}
+
+impl<Net: NetId, M: Marks> PetriNets<Net, M> {
+    /// Inserts a net instance under a label.
+    ///
+    /// Returns the net previously stored under the label, if any.
+    pub fn insert<L: Into<Cow<'static, str>>>(
+        &mut self,
+        label: L,
+        net: PetriNet<Net, M>,
+    ) -> Option<PetriNet<Net, M>> {
+        self.nets.insert(label.into(), net)
+    }
+
+    /// Returns the net instance with the given label, if any.
+    #[must_use]
+    pub fn try_get(&self, label: &str) -> Option<&PetriNet<Net, M>> {
+        self.nets.get(label)
+    }
+
+    /// Returns the net instance with the given label.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if there is no net with this label.
+    #[must_use]
+    pub fn get(&self, label: &str) -> &PetriNet<Net, M> {
+        self.try_get(label).unwrap_or_else(|| {
+            panic!(
+                "Net `{}` with label `{}` not found. Make sure you add it first.",
+                std::any::type_name::<Net>(),
+                label
+            )
+        })
+    }
+
+    /// Returns an iterator over the labels of the net instances.
+    pub fn labels(&self) -> impl Iterator<Item = &str> {
+        self.nets.keys().map(AsRef::as_ref)
+    }
+
+    /// Spawns new token for the net instance with the given label.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if there is no net with this label.
+    #[must_use]
+    pub fn spawn_token(&self, label: &str) -> Token<Net, M> {
+        self.get(label).spawn_token()
+    }
+
+    /// Fires a transition of the net instance with the given label.
+    ///
+    /// ## Errors
+    ///
+    /// Returns a [`FireError`] if the transition could not be fired.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if there is no net with this label.
+    pub fn fire<T: Trans<Net>>(
+        &self,
+        label: &str,
+        token: &mut Token<Net, M>,
+    ) -> Result<(), FireError<Net>> {
+        self.get(label).fire::<T>(token)
+    }
+}
+
+/// Component associating a token with a labeled net instance in [`PetriNets`].
+#[derive(Component, Educe)]
+#[educe(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct NetLabel<Net: NetId> {
+    label: Cow<'static, str>,
+    _net: PhantomData<Net>,
+}
+
+impl<Net: NetId> NetLabel<Net> {
+    /// Returns a new [`NetLabel`].
+    pub fn new<L: Into<Cow<'static, str>>>(label: L) -> Self {
+        Self {
+            label: label.into(),
+            _net: PhantomData,
+        }
+    }
+
+    /// Returns the label of the net instance.
+    #[must_use]
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+}
+
+/// System sets for ordering the systems working with a [`PetriNet`].
+///
+/// Configured by [`PetriNetAppExt::add_petri_systems`] to run in the order
+/// [`Spawn`](PetriNetSet::Spawn), [`Mark`](PetriNetSet::Mark), [`Fire`](PetriNetSet::Fire),
+/// with commands applied between spawning and marking.
+#[derive(SystemSet, Educe)]
+#[educe(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum PetriNetSet<Net: NetId> {
+    /// Spawning and despawning tokens.
+    Spawn,
+    /// Marking tokens, e.g. with the initial marking.
+    Mark,
+    /// Firing transitions.
+    Fire,
+    #[doc(hidden)]
+    _Net(Infallible, PhantomData<Net>),
+}
+
+/// Extension trait for adding [`PetriNet`] scheduling to an [`App`].
+pub trait PetriNetAppExt {
+    /// Configures the [`PetriNetSet`]s of the net in the [`Update`] schedule.
+    fn add_petri_systems<Net: NetId>(&mut self) -> &mut Self;
+
+    /// Configures the [`PetriNetSet`]s of the net in the given schedule.
+    fn add_petri_systems_to<Net: NetId>(&mut self, schedule: impl ScheduleLabel) -> &mut Self;
+
+    /// Registers the [`Token`]s of the net for reflection.
+    ///
+    /// For their type paths to be stable, the net has to implement [`TypePath`].
+    fn register_token<Net: NetId + TypePath, M: Marks + TypePath>(&mut self) -> &mut Self;
+
+    /// Registers the [`Token`]s of the net for reflection, along with the serialization
+    /// of their marking, so that they can be saved in scenes.
+    #[cfg(feature = "serde")]
+    fn register_token_serde<Net, M>(&mut self) -> &mut Self
+    where
+        Net: NetId + TypePath,
+        M: Marks + TypePath + Serialize + DeserializeOwned;
+}
+
+impl PetriNetAppExt for App {
+    fn add_petri_systems<Net: NetId>(&mut self) -> &mut Self {
+        self.add_petri_systems_to::<Net>(Update)
+    }
+
+    fn add_petri_systems_to<Net: NetId>(&mut self, schedule: impl ScheduleLabel) -> &mut Self {
+        let schedule = schedule.intern();
+        self.configure_sets(
+            schedule,
+            (
+                PetriNetSet::<Net>::Spawn,
+                PetriNetSet::<Net>::Mark,
+                PetriNetSet::<Net>::Fire,
+            )
+                .chain(),
+        )
+        .add_systems(
+            schedule,
+            apply_deferred
+                .after(PetriNetSet::<Net>::Spawn)
+                .before(PetriNetSet::<Net>::Mark),
+        )
+    }
+
+    fn register_token<Net: NetId + TypePath, M: Marks + TypePath>(&mut self) -> &mut Self {
+        self.register_type::<Token<Net, M>>()
+    }
+
+    #[cfg(feature = "serde")]
+    fn register_token_serde<Net, M>(&mut self) -> &mut Self
+    where
+        Net: NetId + TypePath,
+        M: Marks + TypePath + Serialize + DeserializeOwned,
+    {
+        self.register_token::<Net, M>();
+        Token::<Net, M>::register_serde(&mut self.world.resource::<AppTypeRegistry>().write());
+        self
+    }
+}
+
+/// Extension trait for spawning [`Token`]s with [`Commands`].
+pub trait PetriNetCommandsExt {
+    /// Spawns `count` tokens of the net, each marked by `init`.
+    ///
+    /// The tokens are spawned in a single batch once the commands are applied.
+    fn spawn_tokens<Net: NetId, M: Marks>(
+        &mut self,
+        net: &PetriNet<Net, M>,
+        count: usize,
+        init: impl Fn(&PetriNet<Net, M>, &mut Token<Net, M>),
+    );
+}
+
+impl PetriNetCommandsExt for Commands<'_, '_> {
+    fn spawn_tokens<Net: NetId, M: Marks>(
+        &mut self,
+        net: &PetriNet<Net, M>,
+        count: usize,
+        init: impl Fn(&PetriNet<Net, M>, &mut Token<Net, M>),
+    ) {
+        let tokens: Vec<_> = (0..count)
+            .map(|_| {
+                let mut token = net.spawn_token();
+                init(net, &mut token);
+                token
+            })
+            .collect();
+        self.spawn_batch(tokens);
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::needless_pass_by_value)]
+mod tests {
+    use bevy_app::{App, Update};
+    use bevy_ecs::prelude::*;
+    use bevy_reflect::TypePath;
+
+    use crate::{
+        NetId, NetLabel, Nn, PetriNet, PetriNetAppExt, PetriNetCommandsExt, PetriNetPlugin,
+        PetriNetSet, PetriNets, Pn, Tn, Token, W,
+    };
+
+    type Net = Nn<0>;
+
+    #[derive(Resource, Default)]
+    struct Log(Vec<&'static str>);
+
+    fn spawn(mut commands: Commands, net: Res<PetriNet<Net>>, mut log: ResMut<Log>) {
+        commands.spawn(net.spawn_token());
+        log.0.push("spawn");
+    }
+
+    fn mark(net: Res<PetriNet<Net>>, mut tokens: Query<&mut Token<Net>>, mut log: ResMut<Log>) {
+        for mut token in &mut tokens {
+            net.mark::<Pn<0>>(&mut token, 1);
+            log.0.push("mark");
+        }
+    }
+
+    fn fire(net: Res<PetriNet<Net>>, mut tokens: Query<&mut Token<Net>>, mut log: ResMut<Log>) {
+        for mut token in &mut tokens {
+            if net.fire::<Tn<0>>(&mut token).is_ok() {
+                log.0.push("fire");
+            }
+        }
+    }
+
+    #[test]
+    fn test_sets_run_in_order() {
+        let mut app = App::new();
+        app.add_plugins(PetriNetPlugin::<Net>::new(|net| {
+            net.add_place::<Pn<0>>()
+                .add_place::<Pn<1>>()
+                .add_trans::<Tn<0>, (Pn<0>, W<1>), (Pn<1>, W<1>)>()
+        }))
+        .add_petri_systems::<Net>()
+        .init_resource::<Log>()
+        .add_systems(
+            Update,
+            (
+                fire.in_set(PetriNetSet::<Net>::Fire),
+                mark.in_set(PetriNetSet::<Net>::Mark),
+                spawn.in_set(PetriNetSet::<Net>::Spawn),
+            ),
+        );
+        app.update();
+        assert_eq!(app.world.resource::<Log>().0, ["spawn", "mark", "fire"]);
+    }
+
+    #[test]
+    fn test_initial_token() {
+        let mut app = App::new();
+        app.add_plugins(
+            PetriNetPlugin::<Net>::new(|net| net.add_place::<Pn<0>>().add_place::<Pn<1>>())
+                .with_initial_token(|net, token| net.mark::<Pn<1>>(token, 2)),
+        );
+        app.update();
+        let net = app.world.resource::<PetriNet<Net>>();
+        let tokens: Vec<_> = app
+            .world
+            .iter_entities()
+            .filter_map(|entity| entity.get::<Token<Net>>())
+            .collect();
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(net.marks::<Pn<0>>(tokens[0]), 0);
+        assert_eq!(net.marks::<Pn<1>>(tokens[0]), 2);
+    }
+
+    #[test]
+    fn test_token_round_trips_through_reflection() {
+        let mut app = App::new();
+        app.add_plugins(
+            PetriNetPlugin::<Net>::new(|net| net.add_place::<Pn<0>>().add_place::<Pn<1>>())
+                .with_initial_token(|net, token| net.mark::<Pn<1>>(token, 2)),
+        )
+        .register_token::<Net, usize>();
+        app.update();
+        let registry = app.world.resource::<AppTypeRegistry>().clone();
+        let registry = registry.read();
+        let reflect_component = registry
+            .get_type_data::<ReflectComponent>(std::any::TypeId::of::<Token<Net>>())
+            .unwrap();
+        let mut tokens = app.world.query::<(Entity, &Token<Net>)>();
+        let (entity, token) = tokens.single(&app.world);
+        let token = token.clone();
+        let reflected = reflect_component
+            .reflect(app.world.entity(entity))
+            .unwrap()
+            .clone_value();
+        let mut copy = app.world.spawn_empty();
+        reflect_component.insert(&mut copy, &*reflected);
+        assert_eq!(copy.get::<Token<Net>>(), Some(&token));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_token_round_trips_through_scene() {
+        use bevy_scene::serde::SceneDeserializer;
+        use bevy_scene::DynamicScene;
+        use serde::de::DeserializeSeed;
+
+        let mut app = App::new();
+        app.add_plugins(
+            PetriNetPlugin::<Net>::new(|net| net.add_place::<Pn<0>>().add_place::<Pn<1>>())
+                .with_initial_token(|net, token| net.mark::<Pn<1>>(token, 2)),
+        )
+        .register_token_serde::<Net, usize>();
+        app.update();
+        let registry = app.world.resource::<AppTypeRegistry>().clone();
+        let scene = DynamicScene::from_world(&app.world);
+        let ron = scene.serialize_ron(&registry).unwrap();
+        let mut deserializer = ron::de::Deserializer::from_str(&ron).unwrap();
+        let loaded = SceneDeserializer {
+            type_registry: &registry.read(),
+        }
+        .deserialize(&mut deserializer)
+        .unwrap();
+        let mut world = World::new();
+        world.insert_resource(registry);
+        loaded
+            .write_to_world(&mut world, &mut bevy_utils::HashMap::default())
+            .unwrap();
+        let net = app.world.resource::<PetriNet<Net>>();
+        let tokens: Vec<_> = world.query::<&Token<Net>>().iter(&world).collect();
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(net.marks::<Pn<0>>(tokens[0]), 0);
+        assert_eq!(net.marks::<Pn<1>>(tokens[0]), 2);
+    }
+
+    #[test]
+    fn test_plugin_accepts_net_without_type_path() {
+        enum Plain {}
+        impl NetId for Plain {}
+
+        let mut app = App::new();
+        app.add_plugins(PetriNetPlugin::<Plain>::new(PetriNet::add_place::<Pn<0>>));
+        assert!(app.world.contains_resource::<PetriNet<Plain>>());
+    }
+
+    #[test]
+    fn test_token_from_world_spawns_token_of_net() {
+        let mut app = App::new();
+        app.add_plugins(PetriNetPlugin::<Net>::new(|net| {
+            net.add_place::<Pn<0>>().add_place::<Pn<1>>()
+        }));
+        let token = Token::<Net>::from_world(&mut app.world);
+        assert_eq!(token, app.world.resource::<PetriNet<Net>>().spawn_token());
+        assert_eq!(token.to_marking().len(), 2);
+    }
+
+    #[test]
+    fn test_token_type_path_is_stable() {
+        assert_eq!(
+            Token::<Net>::type_path(),
+            "petnat::net::token::Token<petnat::net::Nn<0>, usize>"
+        );
+        assert_eq!(Token::<Net>::short_type_path(), "Token<Nn<0>, usize>");
+    }
+
+    #[test]
+    fn test_spawn_tokens() {
+        let mut app = App::new();
+        app.add_plugins(PetriNetPlugin::<Net>::new(|net| {
+            net.add_place::<Pn<0>>().add_place::<Pn<1>>()
+        }))
+        .add_systems(Update, |mut commands: Commands, net: Res<PetriNet<Net>>| {
+            commands.spawn_tokens(&net, 100, |net, token| net.mark::<Pn<1>>(token, 3));
+        });
+        app.update();
+        let net = app.world.resource::<PetriNet<Net>>();
+        let tokens: Vec<_> = app
+            .world
+            .iter_entities()
+            .filter_map(|entity| entity.get::<Token<Net>>())
+            .collect();
+        assert_eq!(tokens.len(), 100);
+        assert!(tokens
+            .iter()
+            .all(|token| net.marks::<Pn<0>>(token) == 0 && net.marks::<Pn<1>>(token) == 3));
+    }
+
+    #[test]
+    fn test_build_from_closure() {
+        let names = vec!["Idle", "Busy"];
+        let mut app = App::new();
+        app.add_plugins(PetriNetPlugin::<Net>::new(move |mut net| {
+            for name in &names {
+                let _ = net.add_place_anon(*name);
+            }
+            net
+        }));
+        let net = app.world.resource::<PetriNet<Net>>();
+        let names: Vec<_> = net.places().map(|(_, meta)| meta.name()).collect();
+        assert_eq!(names, ["Idle", "Busy"]);
+    }
+
+    #[test]
+    fn test_labeled_instances() {
+        fn build(net: PetriNet<Net>) -> PetriNet<Net> {
+            net.add_place::<Pn<0>>()
+                .add_place::<Pn<1>>()
+                .add_trans::<Tn<0>, (Pn<0>, W<1>), (Pn<1>, W<1>)>()
+        }
+        let mut app = App::new();
+        app.add_plugins((
+            PetriNetPlugin::<Net>::new(build)
+                .with_label("a")
+                .with_initial_token(|net, token| net.mark::<Pn<0>>(token, 1)),
+            PetriNetPlugin::<Net>::new(build)
+                .with_label("b")
+                .with_initial_token(|net, token| net.mark::<Pn<0>>(token, 1)),
+        ));
+        app.update();
+        app.world
+            .resource_scope(|world, nets: Mut<PetriNets<Net>>| {
+                let mut tokens = world.query::<(&NetLabel<Net>, &mut Token<Net>)>();
+                for (label, mut token) in tokens.iter_mut(world) {
+                    if label.label() == "a" {
+                        assert!(nets.fire::<Tn<0>>(label.label(), &mut token).is_ok());
+                    }
+                }
+            });
+        let nets = app.world.resource::<PetriNets<Net>>();
+        let mut labels: Vec<_> = nets.labels().collect();
+        labels.sort_unstable();
+        assert_eq!(labels, ["a", "b"]);
+        let mut tokens = app
+            .world
+            .iter_entities()
+            .filter_map(|entity| {
+                Some((entity.get::<NetLabel<Net>>()?, entity.get::<Token<Net>>()?))
+            })
+            .map(|(label, token)| {
+                let net = nets.get(label.label());
+                (
+                    label.label(),
+                    net.marks::<Pn<0>>(token),
+                    net.marks::<Pn<1>>(token),
+                )
+            })
+            .collect::<Vec<_>>();
+        tokens.sort_unstable();
+        assert_eq!(tokens, [("a", 0, 1), ("b", 1, 0)]);
     }
 }